@@ -3,10 +3,13 @@ use std::path::{Path, PathBuf};
 use std::process::{Command, Output, Stdio};
 use std::collections::BTreeMap as Map;
 
-use crate::env::Update;
+use crate::env::{DiffStyle, Update};
 use crate::dependencies::{self, Dependency};
-use crate::manifest::{Bin, Build, Config, Manifest, Name, Package, Workspace};
+use crate::manifest::{Bin, Build, Config, Edition, Manifest, Name, Package, Target, Workspace};
 use crate::error::{Error, Result};
+use crate::message;
+use crate::normalize;
+use crate::options::{self, Options};
 use crate::{Test, TestKind, TestRunner};
 use crate::rustflags;
 
@@ -15,45 +18,88 @@ use serde::Deserialize;
 #[derive(Default)]
 pub struct CargoRunner {
     project: Option<Project>,
+    // Output of the compile_fail tests that were checked together up front,
+    // by test name, until each is taken by its own build.
+    batched: Map<String, Output>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Project {
     pub dir: PathBuf,
     pub(crate) source_dir: PathBuf,
     pub target_dir: PathBuf,
     pub name: String,
+    pub(crate) crate_name: String,
     pub(crate) update: Update,
+    pub(crate) diff: DiffStyle,
+    /// Whether build output goes straight to the terminal (TRYBUILD_STREAM).
+    pub(crate) stream: bool,
     pub has_pass: bool,
     pub(crate) has_compile_fail: bool,
     pub(crate) has_output: bool,
+    /// Features of the crate under test that the tests are built with, or
+    /// `None` if they could not be determined and cargo's defaults apply.
     pub features: Option<Vec<String>>,
+    /// Features that were enabled for `cargo test` but are not declared in the
+    /// crate's manifest, and so are not enabled for the tests.
+    pub dropped_features: Vec<String>,
     pub(crate) workspace: PathBuf,
+    /// Target triple to build for, which `-Z build-std` requires be explicit.
+    pub(crate) target: Option<String>,
+    pub(crate) normalize: normalize::Context,
+    pub(crate) options: Options,
 }
 
 impl TestRunner for CargoRunner {
     type Error = Error;
 
     fn prepare(&mut self, tests: &[Test]) -> Result<()> {
-        let (project, manifest) = prepare_project(tests)?;
+        let project = prepare_project(tests, &Options::default())?;
+        self.prepare_in(&project, tests)
+    }
+
+    fn prepare_in(&mut self, project: &Project, tests: &[Test]) -> Result<()> {
+        let mut manifest = make_manifest(project, tests)?;
+        if let Some(edit_manifest) = &project.options.edit_manifest {
+            (edit_manifest.0)(&mut manifest);
+        }
         let manifest_toml = toml::to_string(&manifest)?;
 
-        let config = make_config();
+        let mut config = toml::Value::try_from(make_config(&project.options))?;
+        for fragment in &project.options.cargo_config {
+            let fragment = toml::from_str(fragment).map_err(Error::CargoConfig)?;
+            merge_config(&mut config, fragment);
+        }
         let config_toml = toml::to_string(&config)?;
 
+        // Cargo 1.39 and newer read config.toml, and warn about the older
+        // extensionless name, including when one was left by a previous run.
         fs::create_dir_all(path!(project.dir / ".cargo"))?;
-        fs::write(path!(project.dir / ".cargo" / "config"), config_toml)?;
+        let _ = fs::remove_file(path!(project.dir / ".cargo" / "config"));
+        fs::write(path!(project.dir / ".cargo" / "config.toml"), config_toml)?;
         fs::write(path!(project.dir / "Cargo.toml"), manifest_toml)?;
         fs::write(path!(project.dir / "main.rs"), b"fn main() {}\n")?;
-
-        build_dependencies(&project)?;
-        self.project = Some(project);
+        write_inline(project, tests)?;
+        write_preludes(project, tests)?;
+
+        build_dependencies(project)?;
+        // The output split out of a batched build is not quite what a build of
+        // its own gives, so tests that are built twice to compare the two are
+        // built one at a time both times.
+        let batch = project.options.batch_compile_fail && !project.options.check_reproducible;
+        if batch && !project.stream {
+            self.batched = build_batch(project, tests)?;
+        }
+        self.project = Some(project.clone());
         Ok(())
     }
 
     fn build(&mut self, test: &Test) -> Result<Output> {
+        if let Some(output) = self.batched.remove(&test.name) {
+            return Ok(output);
+        }
         let project = self.project.as_ref().expect("prepared");
-        build_test(project, &test.name)
+        build_test(project, test)
     }
 
     // SOOOOO, the original code _knows_ about compile vs. run and emits the
@@ -67,21 +113,67 @@ impl TestRunner for CargoRunner {
     }
 }
 
-fn make_config() -> Config {
+fn make_config(options: &Options) -> Config {
     Config {
         build: Build {
-            rustflags: rustflags::make_vec(),
+            rustflags: rustflags::make_vec(options),
         },
     }
 }
 
-pub fn prepare_project(tests: &[Test]) -> Result<(Project, Manifest)> {
-    let metadata = metadata()?;
+// Tables are merged key by key; anything else in `fragment` replaces what was
+// there.
+fn merge_config(config: &mut toml::Value, fragment: toml::Value) {
+    match (config, fragment) {
+        (toml::Value::Table(config), toml::Value::Table(fragment)) => {
+            for (key, value) in fragment {
+                match config.get_mut(&key) {
+                    Some(existing) => merge_config(existing, value),
+                    None => {
+                        config.insert(key, value);
+                    }
+                }
+            }
+        }
+        (config, fragment) => *config = fragment,
+    }
+}
+
+fn crate_name(options: &Options) -> Result<String> {
+    match &options.crate_name {
+        Some(crate_name) => Ok(crate_name.clone()),
+        None => env::var("CARGO_PKG_NAME").map_err(Error::PkgName),
+    }
+}
+
+pub fn source_dir(options: &Options) -> Result<PathBuf> {
+    match &options.manifest_dir {
+        Some(manifest_dir) => Ok(manifest_dir.clone()),
+        None => env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .ok_or(Error::ProjectDir),
+    }
+}
+
+// The directory that `prepare_project` would use, for state kept between runs,
+// without the rest of the work of preparing a project.
+pub fn project_dir(options: &Options) -> Result<PathBuf> {
+    let crate_name = crate_name(options)?;
+    let metadata = metadata(&source_dir(options)?, options)?;
+    Ok(path!(metadata.target_directory / "tests" / crate_name))
+}
+
+pub fn prepare_project(tests: &[Test], options: &Options) -> Result<Project> {
+    if options.jobs == Some(0) {
+        return Err(Error::Jobs);
+    }
+
+    let crate_name = crate_name(options)?;
+    let source_dir = source_dir(options)?;
+    let metadata = metadata(&source_dir, options)?;
     let target_dir = metadata.target_directory;
     let workspace = metadata.workspace_root;
 
-    let crate_name = env::var("CARGO_PKG_NAME").map_err(Error::PkgName)?;
-
     let mut has_pass = false;
     let mut has_compile_fail = false;
     let mut has_output = false;
@@ -93,131 +185,323 @@ pub fn prepare_project(tests: &[Test]) -> Result<(Project, Manifest)> {
         }
     }
 
-    let source_dir = env::var_os("CARGO_MANIFEST_DIR")
-        .map(PathBuf::from)
-        .ok_or(Error::ProjectDir)?;
+    let declared_features = dependencies::get_manifest(&source_dir).all_features();
+    let mut features = crate::features::find(&declared_features);
+    let mut dropped_features = Vec::new();
+    if let Some(enabled_features) = &mut features {
+        let (declared, dropped) = enabled_features
+            .drain(..)
+            .partition(|f| declared_features.contains_key(f));
+        *enabled_features = declared;
+        dropped_features = dropped;
+    }
+    if options.strict_features && !dropped_features.is_empty() {
+        return Err(Error::DroppedFeatures(dropped_features));
+    }
+
+    let target = if options.build_std.is_empty() {
+        None
+    } else {
+        Some(nightly_host()?)
+    };
 
-    let features = crate::features::find();
+    let normalize = normalize::Context::new(&source_dir, options);
 
-    let mut project = Project {
+    let project = Project {
         dir: path!(target_dir / "tests" / crate_name),
         source_dir,
         target_dir,
         name: format!("{}-tests", crate_name),
-        update: crate::env::Update::env()?,
+        crate_name,
+        update: Update::env()?,
+        diff: DiffStyle::env()?,
+        stream: crate::env::enabled("TRYBUILD_STREAM"),
         has_pass,
         has_compile_fail,
         has_output,
         features,
+        dropped_features,
         workspace,
+        target,
+        normalize,
+        options: options.clone(),
     };
 
-    let manifest = make_manifest(crate_name, &project, tests)?;
-    if let Some(enabled_features) = &mut project.features {
-        enabled_features.retain(|f| manifest.features.contains_key(f));
-    }
-
-    Ok((project, manifest))
+    Ok(project)
 }
 
-fn make_manifest(
-    crate_name: String,
-    project: &Project,
-    tests: &[Test],
-) -> Result<Manifest> {
+fn make_manifest(project: &Project, tests: &[Test]) -> Result<Manifest> {
+    let crate_name = &project.crate_name;
     let source_manifest = dependencies::get_manifest(&project.source_dir);
     let workspace_manifest = dependencies::get_workspace_manifest(&project.workspace);
 
-    let features = source_manifest
-        .features
-        .keys()
-        .map(|feature| {
-            let enable = format!("{}/{}", crate_name, feature);
-            (feature.clone(), vec![enable])
-        })
-        .collect();
+    let source_features = source_manifest.all_features();
+    let lints = source_manifest.lints(&workspace_manifest);
+
+    let edition = match &project.options.edition {
+        Some(edition) => Edition::parse(edition).ok_or_else(|| Error::Edition(edition.clone()))?,
+        None => source_manifest.package.edition,
+    };
 
     let mut manifest = Manifest {
         package: Package {
             name: project.name.clone(),
             version: "0.0.0".to_owned(),
-            edition: source_manifest.package.edition,
+            edition,
             publish: false,
+            rest: Map::new(),
         },
-        features,
+        features: Map::new(),
         dependencies: Map::new(),
+        target: Map::new(),
+        lints,
         bins: Vec::new(),
-        workspace: Some(Workspace {}),
+        // The test project is a workspace of its own, so the resolver the
+        // crate is built with has to be set on it explicitly.
+        workspace: Some(Workspace {
+            resolver: workspace_manifest
+                .workspace
+                .resolver
+                .or(source_manifest.package.resolver),
+        }),
         // Within a workspace, only the [patch] and [replace] sections in
         // the workspace root's Cargo.toml are applied by Cargo.
         patch: workspace_manifest.patch,
         replace: workspace_manifest.replace,
+        rest: Map::new(),
     };
 
     manifest.dependencies.extend(source_manifest.dependencies);
-    manifest
-        .dependencies
-        .extend(source_manifest.dev_dependencies);
+    dependencies::merge(&mut manifest.dependencies, source_manifest.dev_dependencies);
+
+    for (cfg, target) in source_manifest.target {
+        let mut dependencies = target.dependencies;
+        dependencies::merge(&mut dependencies, target.dev_dependencies);
+        manifest.target.insert(cfg, Target { dependencies });
+    }
+
+    for (feature, values) in &source_features {
+        let enable = forward_feature(&manifest, crate_name, feature, values, &source_features);
+        manifest.features.insert(feature.clone(), enable);
+    }
+
     manifest.dependencies.insert(
-        crate_name,
+        crate_name.clone(),
         Dependency {
             version: None,
             path: Some(project.source_dir.clone()),
             default_features: false,
-            features: Vec::new(),
+            features: project.options.self_features.clone(),
             rest: Map::new(),
         },
     );
 
+    for member in &project.options.workspace_members {
+        let path = project.source_dir.join(member);
+        let member = dependencies::try_get_manifest(&path).map_err(|err| {
+            Error::WorkspaceMember(path.join("Cargo.toml"), err.to_string())
+        })?;
+        let mut name = member.package.name;
+        if name.is_empty() {
+            name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        }
+        manifest.dependencies.insert(
+            name,
+            Dependency {
+                version: None,
+                path: Some(path),
+                default_features: true,
+                features: Vec::new(),
+                rest: Map::new(),
+            },
+        );
+    }
+
     manifest.bins.push(Bin {
         name: Name(project.name.to_owned()),
         path: Path::new("main.rs").to_owned(),
     });
 
-    for (i, test) in tests.iter().enumerate() {
+    for test in tests {
         manifest.bins.push(Bin {
-            name: Name(Test::gen_name(i)),
-            path: project.source_dir.join(&test.path),
+            name: Name(test.name.clone()),
+            path: match &project.options.prelude {
+                Some(_) => prelude_path(project, test),
+                None => bin_path(project, test),
+            },
         });
     }
 
     Ok(manifest)
 }
 
+// Symlinked test files are built from the file they point to. Cargo and glob
+// do not agree on whether to follow links, so resolve them up front.
+pub fn bin_path(project: &Project, test: &Test) -> PathBuf {
+    if test.inline.is_some() {
+        return inline_path(project, test);
+    }
+    let path = project.source_dir.join(&test.path);
+    fs::canonicalize(&path).unwrap_or(path)
+}
+
+fn write_inline(project: &Project, tests: &[Test]) -> Result<()> {
+    let mut tests = tests.iter().filter_map(|test| Some((test, test.inline.as_ref()?))).peekable();
+    if tests.peek().is_none() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(path!(project.dir / "inline"))?;
+    for (test, inline) in tests {
+        fs::write(inline_path(project, test), &inline.source)?;
+    }
+    Ok(())
+}
+
+pub fn inline_path(project: &Project, test: &Test) -> PathBuf {
+    path!(project.dir / "inline" / format!("{}.rs", test.name))
+}
+
+// With a prelude, each test is built from a file of its own that includes the
+// test after the prelude. Spans within the included test refer to the test's
+// own file and lines.
+fn write_preludes(project: &Project, tests: &[Test]) -> Result<()> {
+    let prelude = match &project.options.prelude {
+        Some(prelude) => prelude,
+        None => return Ok(()),
+    };
+
+    fs::create_dir_all(path!(project.dir / "prelude"))?;
+    for test in tests {
+        let content = format!(
+            "{}\ninclude!({:?});\n",
+            prelude.trim_end(),
+            bin_path(project, test),
+        );
+        fs::write(prelude_path(project, test), content)?;
+    }
+    Ok(())
+}
+
+pub fn prelude_path(project: &Project, test: &Test) -> PathBuf {
+    path!(project.dir / "prelude" / format!("{}.rs", test.name))
+}
+
+// Each feature of the test project enables the same feature of the crate under
+// test. The test project also has its own copies of the crate's dependencies,
+// for the tests to use, so it enables the same optional dependencies and
+// dependency features that the crate's feature does, in the same `dep:x`,
+// `x/y` and `x?/y` syntax. Dependencies that the merge with dev-dependencies
+// made non-optional are referred to accordingly.
+fn forward_feature(
+    manifest: &Manifest,
+    crate_name: &str,
+    feature: &str,
+    values: &[String],
+    features: &Map<String, Vec<String>>,
+) -> Vec<String> {
+    let dependency = |name: &str| {
+        manifest.dependencies.get(name).or_else(|| {
+            manifest.target.values().find_map(|target| target.dependencies.get(name))
+        })
+    };
+    let optional = |name: &str| dependency(name).map_or(false, Dependency::is_optional);
+
+    let mut enable = vec![format!("{}/{}", crate_name, feature)];
+    for value in values {
+        let forwarded = if let Some(name) = value.strip_prefix("dep:") {
+            if optional(name) { Some(value.clone()) } else { None }
+        } else if let Some((name, dep_feature)) = value.split_once('/') {
+            match name.strip_suffix('?') {
+                Some(name) if optional(name) => Some(value.clone()),
+                Some(name) if dependency(name).is_some() => {
+                    Some(format!("{}/{}", name, dep_feature))
+                }
+                Some(_) => None,
+                None if dependency(name).is_some() => Some(value.clone()),
+                None => None,
+            }
+        } else if features.contains_key(value) || optional(value) {
+            Some(value.clone())
+        } else {
+            None
+        };
+        enable.extend(forwarded);
+    }
+    enable
+}
+
 #[derive(Deserialize)]
 pub struct Metadata {
     pub target_directory: PathBuf,
     pub workspace_root: PathBuf,
 }
 
-fn raw_cargo() -> Command {
-    Command::new(option_env!("CARGO").unwrap_or("cargo"))
+fn raw_cargo(options: &Options) -> Command {
+    match &options.cargo_bin {
+        Some(cargo_bin) => Command::new(cargo_bin),
+        None => Command::new(option_env!("CARGO").unwrap_or("cargo")),
+    }
 }
 
 fn cargo(project: &Project) -> Command {
-    let mut cmd = raw_cargo();
+    let mut cmd = raw_cargo(&project.options);
     cmd.current_dir(&project.dir);
     cmd.env("CARGO_TARGET_DIR", &project.target_dir);
-    rustflags::set_env(&mut cmd);
+    if let Some(wrapper) = &project.options.rustc_wrapper {
+        cmd.env("RUSTC_WRAPPER", wrapper);
+    }
+    if let Some(incremental) = project.options.incremental {
+        cmd.env("CARGO_INCREMENTAL", if incremental { "1" } else { "0" });
+    }
+    if let Some(jobs) = project.options.jobs {
+        cmd.env("CARGO_BUILD_JOBS", jobs.to_string());
+    }
+    rustflags::set_env(&mut cmd, &project.options, &[]);
     cmd
 }
 
 pub fn build_dependencies(project: &Project) -> Result<()> {
-    let status = cargo(project)
-        .arg(if project.has_pass || project.has_output { "build" } else { "check" })
-        .arg("--bin")
-        .arg(&project.name)
+    let status = dependencies(project)
         .status()
         .map_err(Error::Cargo)?;
 
     if status.success() {
+        message::cargo_warnings(project, &cargo_warnings(project)?);
         Ok(())
     } else {
         Err(Error::CargoFail)
     }
 }
 
-pub fn build_test(project: &Project, name: &str) -> Result<Output> {
+fn dependencies(project: &Project) -> Command {
+    let mut cmd = cargo(project);
+    cmd.arg(if project.has_pass || project.has_output { "build" } else { "check" })
+        .arg("--bin")
+        .arg(&project.name)
+        .args(build_std(project))
+        .args(&project.options.cargo_args);
+    cmd
+}
+
+// Cargo's own warnings about the generated project, like unused manifest keys,
+// are repeated by every build, even one with nothing left to do. Going over the
+// dependency build again with the compiler's diagnostics sent to stdout as JSON
+// leaves only those on stderr, without taking over the output of the real
+// build.
+fn cargo_warnings(project: &Project) -> Result<String> {
+    let output = dependencies(project)
+        .arg("--message-format=json")
+        .arg("--color=never")
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(Error::Cargo)?;
+
+    Ok(normalize::cargo_warnings(&String::from_utf8_lossy(&output.stderr)))
+}
+
+pub fn build_test(project: &Project, test: &Test) -> Result<Output> {
     let _ = cargo(project)
         .arg("clean")
         .arg("--package")
@@ -227,46 +511,316 @@ pub fn build_test(project: &Project, name: &str) -> Result<Output> {
         .stderr(Stdio::null())
         .status();
 
-    cargo(project)
-        .arg(if project.has_pass || project.has_output { "build" } else { "check" })
+    let mut cmd = cargo(project);
+    rustflags::set_env(&mut cmd, &project.options, test_rustflags(project, test));
+    cmd.arg(if project.has_pass || project.has_output { "build" } else { "check" })
         .arg("--bin")
-        .arg(name)
+        .arg(&test.name)
         .args(features(project))
-        .arg("--quiet")
+        .args(build_std(project));
+
+    if project.stream {
+        let status = cmd
+            .args(&project.options.cargo_args)
+            .status()
+            .map_err(Error::Cargo)?;
+        return Ok(Output { status, stdout: Vec::new(), stderr: Vec::new() });
+    }
+
+    cmd.arg("--quiet")
         .arg("--color=never")
+        .args(&project.options.cargo_args)
         .output()
         .map_err(Error::Cargo)
 }
 
+// Checks the compile_fail tests in one go and splits the diagnostics up by
+// test, in the same form as when each is built on its own: the rendered
+// compiler messages followed by cargo's "could not compile" line. Only tests
+// that failed are returned, with the status of the whole check; the others
+// are left to be built individually.
+fn build_batch(project: &Project, tests: &[Test]) -> Result<Map<String, Output>> {
+    let batch: Vec<&Test> = tests
+        .iter()
+        .filter(|test| matches!(test.kind, TestKind::CompileFail))
+        .filter(|test| test_rustflags(project, test).is_empty())
+        .collect();
+    if batch.is_empty() {
+        return Ok(Map::new());
+    }
+
+    let _ = cargo(project)
+        .arg("clean")
+        .arg("--package")
+        .arg(&project.name)
+        .arg("--color=never")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+
+    let mut cmd = cargo(project);
+    cmd.arg(if project.has_pass || project.has_output { "build" } else { "check" })
+        .arg("--keep-going")
+        .arg("--message-format=json");
+    for test in &batch {
+        cmd.arg("--bin").arg(&test.name);
+    }
+    let output = cmd
+        .args(features(project))
+        .args(build_std(project))
+        .arg("--quiet")
+        .arg("--color=never")
+        .args(&project.options.cargo_args)
+        .output()
+        .map_err(Error::Cargo)?;
+
+    let names: Vec<&str> = batch.iter().map(|test| test.name.as_str()).collect();
+    Ok(split_batch(&names, &output.stdout, &output.stderr)
+        .into_iter()
+        .map(|(name, stderr)| {
+            let output = Output {
+                status: output.status,
+                stdout: Vec::new(),
+                stderr: stderr.into_bytes(),
+            };
+            (name.to_owned(), output)
+        })
+        .collect())
+}
+
+// Picks out the stderr of each of the `names` tests that failed to build from
+// the output of building them together: the rendered diagnostics from the JSON
+// messages on stdout, followed by cargo's own lines about the test's bin.
+fn split_batch<'a>(names: &[&'a str], stdout: &[u8], stderr: &[u8]) -> Map<&'a str, String> {
+    let mut rendered: Map<&str, String> = Map::new();
+    let mut failed: Vec<&str> = Vec::new();
+    for line in String::from_utf8_lossy(stdout).lines() {
+        let message: serde_json::Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(_) => continue,
+        };
+        if message["reason"] != "compiler-message" {
+            continue;
+        }
+        let name = match names.iter().find(|&&name| message["target"]["name"] == name) {
+            Some(&name) => name,
+            None => continue,
+        };
+        if message["message"]["level"] == "error" && !failed.contains(&name) {
+            failed.push(name);
+        }
+        if let Some(text) = message["message"]["rendered"].as_str() {
+            rendered.entry(name).or_default().push_str(text);
+        }
+    }
+
+    for line in String::from_utf8_lossy(stderr).lines() {
+        for &name in names {
+            if line.contains(&format!("(bin \"{}\")", name)) {
+                rendered.entry(name).or_default().push_str(&format!("{}\n", line));
+            }
+        }
+    }
+
+    failed
+        .into_iter()
+        .filter_map(|name| Some((name, rendered.remove(name)?)))
+        .collect()
+}
+
 pub fn run_test(project: &Project, test: &Test) -> Result<Output> {
-    cargo(project)
-        .arg("run")
+    let mut cmd = cargo(project);
+    rustflags::set_env(&mut cmd, &project.options, test_rustflags(project, test));
+    if project.options.backtrace {
+        cmd.env("RUST_BACKTRACE", "1");
+    }
+
+    cmd.arg("run")
         .arg("--bin")
         .arg(&test.name)
         .args(features(project))
+        .args(build_std(project))
         .arg("--quiet")
         .arg("--color=never")
+        .args(&project.options.cargo_args)
         .output()
         .map_err(Error::Cargo)
 }
 
-pub fn metadata() -> Result<Metadata> {
-    let output = raw_cargo()
+// The run has to use the same flags as the build, or `cargo run` would build
+// the test again without them.
+fn test_rustflags<'a>(project: &'a Project, test: &Test) -> &'a [String] {
+    let base_dir = project.options.base_dir.as_deref();
+    options::for_test(&project.options.rustflags, &test.path, base_dir).map_or(&[], Vec::as_slice)
+}
+
+pub fn metadata(manifest_dir: &Path, options: &Options) -> Result<Metadata> {
+    let output = raw_cargo(options)
+        .current_dir(manifest_dir)
         .arg("metadata")
         .arg("--format-version=1")
         .output()
         .map_err(Error::Cargo)?;
 
-    serde_json::from_slice(&output.stdout).map_err(Error::Metadata)
+    parse_metadata(&output.stdout).map_err(|error| {
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        Error::Metadata(error, stdout, stderr)
+    })
+}
+
+// Some cargo configurations and plugins print to stdout ahead of the metadata,
+// which itself is a single line of JSON.
+fn parse_metadata(stdout: &[u8]) -> serde_json::Result<Metadata> {
+    let error = match serde_json::from_slice(stdout) {
+        Ok(metadata) => return Ok(metadata),
+        Err(error) => error,
+    };
+
+    stdout
+        .split(|&b| b == b'\n')
+        .rev()
+        .filter(|line| line.starts_with(b"{"))
+        .find_map(|line| serde_json::from_slice(line).ok())
+        .ok_or(error)
 }
 
 fn features(project: &Project) -> Vec<String> {
-    match &project.features {
-        Some(features) => vec![
-            "--no-default-features".to_owned(),
-            "--features".to_owned(),
-            features.join(","),
+    // Every feature of the test project forwards to the same feature of the
+    // crate under test, so this enables all of the latter.
+    if project.options.all_features {
+        return vec!["--all-features".to_owned()];
+    }
+
+    let mut args = Vec::new();
+    if let Some(features) = &project.features {
+        if !project.options.keep_default_features {
+            args.push("--no-default-features".to_owned());
+        }
+        args.push("--features".to_owned());
+        args.push(features.join(","));
+    }
+    args
+}
+
+fn build_std(project: &Project) -> Vec<String> {
+    match &project.target {
+        Some(target) => vec![
+            "-Z".to_owned(),
+            format!("build-std={}", project.options.build_std.join(",")),
+            "--target".to_owned(),
+            target.clone(),
         ],
         None => vec![],
     }
 }
+
+// Returns the host target triple, after checking that the toolchain accepts
+// unstable cargo flags.
+fn nightly_host() -> Result<String> {
+    let output = Command::new(env::var_os("RUSTC").unwrap_or_else(|| "rustc".into()))
+        .arg("-vV")
+        .output()
+        .map_err(Error::Rustc)?;
+    let verbose_version = String::from_utf8_lossy(&output.stdout);
+
+    let field = |name: &str| {
+        verbose_version
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .map(|value| value.trim().to_owned())
+            .unwrap_or_default()
+    };
+
+    let release = field("release:");
+    let unstable = release.contains("nightly")
+        || release.contains("dev")
+        || env::var_os("RUSTC_BOOTSTRAP").is_some();
+    if !unstable {
+        return Err(Error::BuildStd(release));
+    }
+
+    Ok(field("host:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::options::Options;
+    use std::path::Path;
+
+    #[test]
+    fn metadata_after_noise() {
+        let json = br#"{"target_directory":"/p/target","workspace_root":"/p"}"#;
+        let mut stdout = b"note: using a custom registry\n".to_vec();
+        stdout.extend_from_slice(json);
+        stdout.push(b'\n');
+
+        let metadata = super::parse_metadata(&stdout).unwrap();
+        assert_eq!(metadata.target_directory, Path::new("/p/target"));
+        assert!(super::parse_metadata(b"note: nothing else\n").is_err());
+    }
+
+    #[test]
+    fn merge_config() {
+        let mut config = toml::Value::try_from(super::make_config(&Options::default())).unwrap();
+        let fragment = toml::from_str(
+            r#"
+            build.jobs = 1
+            [source.crates-io]
+            replace-with = "mirror"
+            "#,
+        )
+        .unwrap();
+        super::merge_config(&mut config, fragment);
+        assert_eq!(config["build"]["rustflags"][1].as_str(), Some("dead_code"));
+        assert_eq!(config["build"]["jobs"].as_integer(), Some(1));
+        assert_eq!(config["source"]["crates-io"]["replace-with"].as_str(), Some("mirror"));
+
+        let fragment = toml::from_str("build.rustflags = []").unwrap();
+        super::merge_config(&mut config, fragment);
+        assert_eq!(config["build"]["rustflags"].as_array().map(Vec::len), Some(0));
+    }
+
+    // Abbreviated from `cargo check --keep-going --message-format=json` over a
+    // bin with a type error, one with only a warning and one with no `main`,
+    // along with an error in a bin that is not part of the batch.
+    const BATCH_STDOUT: &str = r#"{"reason":"compiler-message","target":{"name":"trybuild001"},"message":{"rendered":"warning: unused variable: `x`\n --> src/bin/trybuild001.rs:1:17\n\n","level":"warning"}}
+{"reason":"compiler-artifact","target":{"name":"trybuild001"}}
+{"reason":"compiler-message","target":{"name":"trybuild002"},"message":{"rendered":"error[E0601]: `main` function not found in crate `trybuild002`\n --> src/bin/trybuild002.rs:1:15\n\n","level":"error"}}
+{"reason":"compiler-message","target":{"name":"trybuild002"},"message":{"rendered":"For more information about this error, try `rustc --explain E0601`.\n","level":"failure-note"}}
+{"reason":"compiler-message","target":{"name":"trybuild000"},"message":{"rendered":"error[E0308]: mismatched types\n --> src/bin/trybuild000.rs:1:26\n\n","level":"error"}}
+{"reason":"compiler-message","target":{"name":"trybuild000"},"message":{"rendered":"For more information about this error, try `rustc --explain E0308`.\n","level":"failure-note"}}
+{"reason":"compiler-message","target":{"name":"trybuild0000"},"message":{"rendered":"error: unrelated\n\n","level":"error"}}
+{"reason":"build-finished","success":false}
+"#;
+
+    const BATCH_STDERR: &str = "\
+warning: `tests` (bin \"trybuild001\") generated 1 warning
+error: could not compile `tests` (bin \"trybuild002\") due to 1 previous error
+error: could not compile `tests` (bin \"trybuild000\") due to 1 previous error
+error: could not compile `tests` (bin \"trybuild0000\") due to 1 previous error
+";
+
+    #[test]
+    fn split_batch() {
+        let names = ["trybuild000", "trybuild001", "trybuild002"];
+        let mut stdout = b"note: not json\n".to_vec();
+        stdout.extend_from_slice(BATCH_STDOUT.as_bytes());
+        let split = super::split_batch(&names, &stdout, BATCH_STDERR.as_bytes());
+
+        let failed: Vec<&str> = split.keys().copied().collect();
+        assert_eq!(failed, ["trybuild000", "trybuild002"]);
+        assert_eq!(split["trybuild000"],
+            "error[E0308]: mismatched types\n \
+             --> src/bin/trybuild000.rs:1:26\n\
+             \n\
+             For more information about this error, try `rustc --explain E0308`.\n\
+             error: could not compile `tests` (bin \"trybuild000\") due to 1 previous error\n");
+        assert_eq!(split["trybuild002"],
+            "error[E0601]: `main` function not found in crate `trybuild002`\n \
+             --> src/bin/trybuild002.rs:1:15\n\
+             \n\
+             For more information about this error, try `rustc --explain E0601`.\n\
+             error: could not compile `tests` (bin \"trybuild002\") due to 1 previous error\n");
+    }
+}
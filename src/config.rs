@@ -0,0 +1,166 @@
+use crate::error::{Error, Result};
+use crate::TestCases;
+use crate::TestRunner;
+use serde::Deserialize;
+use std::collections::BTreeMap as Map;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// A whole test suite, as declared in a trybuild.toml. The keys are those of
+// the TestCases builder methods, in kebab-case.
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    #[serde(default)]
+    pass: Vec<PathBuf>,
+    #[serde(default)]
+    compile_fail: Vec<PathBuf>,
+    #[serde(default)]
+    output: Vec<PathBuf>,
+    edition: Option<String>,
+    crate_name: Option<String>,
+    #[serde(default)]
+    self_features: Vec<String>,
+    #[serde(default)]
+    all_features: bool,
+    #[serde(default)]
+    cargo_args: Vec<String>,
+    stderr_ext: Option<String>,
+    snapshot_file: Option<PathBuf>,
+    base_dir: Option<PathBuf>,
+    #[serde(default)]
+    strict_whitespace: bool,
+    #[serde(default)]
+    require_stderr: bool,
+    #[serde(default)]
+    separate_warnings: bool,
+    #[serde(default)]
+    tests: Map<PathBuf, TestConfig>,
+}
+
+// Per-test settings, keyed by a test path or glob like the builder methods.
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct TestConfig {
+    flaky: Option<usize>,
+    #[serde(default)]
+    unordered: bool,
+    #[serde(default)]
+    silent: bool,
+    error_count: Option<usize>,
+    #[serde(default)]
+    rustflags: Vec<String>,
+    #[serde(default)]
+    contains: Vec<String>,
+    #[serde(default)]
+    not_contains: Vec<String>,
+}
+
+pub fn load(path: &Path) -> Result<Config> {
+    let content = fs::read_to_string(path).map_err(|err| Error::Open(path.to_owned(), err))?;
+    toml::from_str(&content).map_err(|err| Error::Config(path.to_owned(), err))
+}
+
+impl Config {
+    pub fn apply<R: TestRunner>(self, t: &TestCases<R>) {
+        for path in &self.pass {
+            t.pass(path);
+        }
+        for path in &self.compile_fail {
+            t.compile_fail(path);
+        }
+        for path in &self.output {
+            t.output(path);
+        }
+
+        if let Some(edition) = &self.edition {
+            t.edition(edition);
+        }
+        if let Some(name) = &self.crate_name {
+            t.crate_name(name);
+        }
+        if !self.self_features.is_empty() {
+            t.self_features(&strs(&self.self_features));
+        }
+        if self.all_features {
+            t.all_features();
+        }
+        if !self.cargo_args.is_empty() {
+            t.cargo_args(&strs(&self.cargo_args));
+        }
+        if let Some(ext) = &self.stderr_ext {
+            t.stderr_ext(ext);
+        }
+        if let Some(file) = &self.snapshot_file {
+            t.snapshot_file(file);
+        }
+        if let Some(dir) = &self.base_dir {
+            t.base_dir(dir);
+        }
+        if self.strict_whitespace {
+            t.strict_whitespace();
+        }
+        if self.require_stderr {
+            t.require_stderr();
+        }
+        if self.separate_warnings {
+            t.separate_warnings();
+        }
+
+        for (path, test) in &self.tests {
+            if let Some(retries) = test.flaky {
+                t.flaky(path, retries);
+            }
+            if test.unordered {
+                t.unordered(path);
+            }
+            if test.silent {
+                t.silent(path);
+            }
+            if let Some(count) = test.error_count {
+                t.compile_fail_error_count(path, count);
+            }
+            if !test.rustflags.is_empty() {
+                t.rustflags(path, &strs(&test.rustflags));
+            }
+            for text in &test.contains {
+                t.compile_fail_contains(path, text);
+            }
+            for text in &test.not_contains {
+                t.compile_fail_not_contains(path, text);
+            }
+        }
+    }
+}
+
+fn strs(strings: &[String]) -> Vec<&str> {
+    strings.iter().map(String::as_str).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use std::path::Path;
+
+    #[test]
+    fn parse() {
+        let config: Config = toml::from_str(
+            "compile-fail = [\"tests/ui/fail/*.rs\"]\n\
+             pass = [\"tests/ui/pass/*.rs\"]\n\
+             edition = \"2021\"\n\
+             \n\
+             [tests.\"tests/ui/fail/leak.rs\"]\n\
+             not-contains = [\"Internal\"]\n\
+             error-count = 2\n",
+        )
+        .unwrap();
+        assert_eq!(config.compile_fail, [Path::new("tests/ui/fail/*.rs")]);
+        assert_eq!(config.edition.as_deref(), Some("2021"));
+        let test = &config.tests[Path::new("tests/ui/fail/leak.rs")];
+        assert_eq!(test.not_contains, ["Internal"]);
+        assert_eq!(test.error_count, Some(2));
+
+        let typo = toml::from_str::<Config>("compile_fail = []\n");
+        assert!(typo.is_err());
+    }
+}
@@ -14,13 +14,17 @@ pub fn get_manifest(manifest_dir: &Path) -> Manifest {
     try_get_manifest(manifest_dir).unwrap_or_default()
 }
 
-fn try_get_manifest(manifest_dir: &Path) -> Result<Manifest, Error> {
+pub fn try_get_manifest(manifest_dir: &Path) -> Result<Manifest, Error> {
     let cargo_toml_path = manifest_dir.join("Cargo.toml");
     let manifest_str = fs::read_to_string(cargo_toml_path)?;
     let mut manifest: Manifest = toml::from_str(&manifest_str)?;
 
     fix_dependencies(&mut manifest.dependencies, manifest_dir);
     fix_dependencies(&mut manifest.dev_dependencies, manifest_dir);
+    for target in manifest.target.values_mut() {
+        fix_dependencies(&mut target.dependencies, manifest_dir);
+        fix_dependencies(&mut target.dev_dependencies, manifest_dir);
+    }
 
     Ok(manifest)
 }
@@ -40,6 +44,86 @@ pub fn try_get_workspace_manifest(manifest_dir: &Path) -> Result<WorkspaceManife
     Ok(manifest)
 }
 
+// Adds the dev-dependencies to the normal dependencies. A crate may be both, in
+// which case the two entries are combined the way cargo resolves them for the
+// crate's own tests.
+pub fn merge(dependencies: &mut Map<String, Dependency>, dev_dependencies: Map<String, Dependency>) {
+    for (name, dev) in dev_dependencies {
+        let merged = match dependencies.remove(&name) {
+            Some(normal) => normal.merge(dev),
+            None => dev,
+        };
+        dependencies.insert(name, merged);
+    }
+}
+
+impl Manifest {
+    // The crate's features, including the implicit feature of each optional
+    // dependency that no feature refers to as `dep:name`.
+    pub fn all_features(&self) -> Map<String, Vec<String>> {
+        let mut features = self.features.clone();
+
+        let explicit = |name: &str| {
+            let dep = format!("dep:{}", name);
+            self.features.values().flatten().any(|value| *value == dep)
+        };
+        let targets = self.target.values().map(|target| &target.dependencies);
+        for dependencies in Some(&self.dependencies).into_iter().chain(targets) {
+            for (name, dependency) in dependencies {
+                if dependency.is_optional() && !explicit(name) {
+                    let implicit = vec![format!("dep:{}", name)];
+                    features.entry(name.clone()).or_insert(implicit);
+                }
+            }
+        }
+
+        features
+    }
+
+    // The lint levels configured for the crate, resolving `workspace = true`
+    // since the test project is not part of the crate's workspace.
+    pub fn lints(&self, workspace: &WorkspaceManifest) -> Map<String, Value> {
+        if self.lints.get("workspace") == Some(&Value::Boolean(true)) {
+            workspace.workspace.lints.clone()
+        } else {
+            self.lints.clone()
+        }
+    }
+}
+
+impl Dependency {
+    pub(crate) fn is_optional(&self) -> bool {
+        self.rest.get("optional") == Some(&Value::Boolean(true))
+    }
+
+    // Features are unioned and default features are on if either entry has
+    // them on. The more specific version requirement wins, as does a path
+    // over none; otherwise the dev-dependency's settings take precedence.
+    fn merge(mut self, dev: Dependency) -> Dependency {
+        for feature in dev.features {
+            if !self.features.contains(&feature) {
+                self.features.push(feature);
+            }
+        }
+        self.default_features |= dev.default_features;
+
+        self.version = match (self.version, dev.version) {
+            (Some(normal), Some(dev)) => {
+                let parts = |version: &str| version.split('.').count();
+                Some(if parts(&normal) > parts(&dev) { normal } else { dev })
+            }
+            (normal, dev) => dev.or(normal),
+        };
+        self.path = dev.path.or(self.path);
+
+        // Dev-dependencies are always enabled for tests, even when the normal
+        // dependency is optional.
+        self.rest.remove("optional");
+        self.rest.extend(dev.rest);
+        self
+    }
+}
+
 fn fix_dependencies(dependencies: &mut Map<String, Dependency>, dir: &Path) {
     dependencies.remove("trybuild");
     for dep in dependencies.values_mut() {
@@ -65,12 +149,22 @@ fn fix_replacements(replacements: &mut Map<String, Patch>, dir: &Path) {
 
 #[derive(Deserialize, Default, Debug)]
 pub struct WorkspaceManifest {
+    #[serde(default)]
+    pub workspace: WorkspaceTable,
     #[serde(default)]
     pub patch: Map<String, RegistryPatch>,
     #[serde(default)]
     pub replace: Map<String, Patch>,
 }
 
+#[derive(Deserialize, Default, Debug)]
+pub struct WorkspaceTable {
+    #[serde(default)]
+    pub resolver: Option<String>,
+    #[serde(default)]
+    pub lints: Map<String, Value>,
+}
+
 #[derive(Deserialize, Default, Debug)]
 pub struct Manifest {
     #[serde(default)]
@@ -81,12 +175,30 @@ pub struct Manifest {
     pub dependencies: Map<String, Dependency>,
     #[serde(default, alias = "dev-dependencies")]
     pub dev_dependencies: Map<String, Dependency>,
+    #[serde(default)]
+    pub target: Map<String, TargetDependencies>,
+    #[serde(default)]
+    pub lints: Map<String, Value>,
+}
+
+// The dependencies under `[target.'cfg(unix)'.dependencies]` and the like,
+// keyed by the target triple or `cfg` expression.
+#[derive(Deserialize, Default, Debug)]
+pub struct TargetDependencies {
+    #[serde(default)]
+    pub dependencies: Map<String, Dependency>,
+    #[serde(default, alias = "dev-dependencies")]
+    pub dev_dependencies: Map<String, Dependency>,
 }
 
 #[derive(Deserialize, Default, Debug)]
 pub struct Package {
+    #[serde(default)]
+    pub name: String,
     #[serde(default)]
     pub edition: Edition,
+    #[serde(default)]
+    pub resolver: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -182,3 +294,106 @@ impl<'de> Deserialize<'de> for Dependency {
         deserializer.deserialize_any(DependencyVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{merge, Manifest, WorkspaceManifest};
+
+    #[test]
+    fn merge_dev_dependencies() {
+        let mut manifest: Manifest = toml::from_str(
+            r#"
+            [dependencies]
+            serde = { version = "1", default-features = false, features = ["alloc"], optional = true }
+            syn = { version = "2.0.10", default-features = false }
+            log = "0.4"
+
+            [dev-dependencies]
+            serde = { version = "1.0.100", features = ["derive", "alloc"] }
+            syn = { version = "2", default-features = false, features = ["full"] }
+            local = { path = "local" }
+            "#,
+        )
+        .unwrap();
+
+        merge(&mut manifest.dependencies, manifest.dev_dependencies);
+        let deps = &manifest.dependencies;
+        assert_eq!(deps.len(), 4);
+
+        let serde = &deps["serde"];
+        assert_eq!(serde.version.as_deref(), Some("1.0.100"));
+        assert_eq!(serde.features, ["alloc", "derive"]);
+        assert!(serde.default_features);
+        assert!(!serde.rest.contains_key("optional"));
+
+        let syn = &deps["syn"];
+        assert_eq!(syn.version.as_deref(), Some("2.0.10"));
+        assert_eq!(syn.features, ["full"]);
+        assert!(!syn.default_features);
+
+        assert_eq!(deps["log"].version.as_deref(), Some("0.4"));
+        assert!(deps["local"].path.is_some());
+    }
+
+    #[test]
+    fn implicit_features() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [dependencies]
+            serde = { version = "1", optional = true }
+            syn = { version = "2", optional = true }
+            log = "0.4"
+
+            [features]
+            derive = ["dep:syn", "serde?/derive"]
+            "#,
+        )
+        .unwrap();
+
+        let features = manifest.all_features();
+        assert_eq!(features.keys().collect::<Vec<_>>(), ["derive", "serde"]);
+        assert_eq!(features["serde"], ["dep:serde"]);
+    }
+
+    #[test]
+    fn resolver() {
+        let workspace: WorkspaceManifest = toml::from_str("[workspace]\nresolver = \"2\"\n").unwrap();
+        assert_eq!(workspace.workspace.resolver.as_deref(), Some("2"));
+
+        let manifest: Manifest = toml::from_str("[package]\nresolver = \"2\"\n").unwrap();
+        assert_eq!(manifest.package.resolver.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn lints() {
+        let workspace: WorkspaceManifest =
+            toml::from_str("[workspace.lints.rust]\nunsafe_code = \"forbid\"\n").unwrap();
+
+        let manifest: Manifest = toml::from_str("[lints]\nworkspace = true\n").unwrap();
+        assert_eq!(manifest.lints(&workspace)["rust"]["unsafe_code"].as_str(), Some("forbid"));
+
+        let manifest: Manifest = toml::from_str("[lints.clippy]\npedantic = \"warn\"\n").unwrap();
+        let lints = manifest.lints(&workspace);
+        assert_eq!(lints.keys().collect::<Vec<_>>(), ["clippy"]);
+
+        let manifest: Manifest = toml::from_str("").unwrap();
+        assert!(manifest.lints(&workspace).is_empty());
+    }
+
+    #[test]
+    fn target_dependencies() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [target.'cfg(unix)'.dependencies]
+            libc = "0.2"
+
+            [target.'cfg(windows)'.dev-dependencies]
+            winapi = "0.3"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manifest.target["cfg(unix)"].dependencies["libc"].version.as_deref(), Some("0.2"));
+        assert!(manifest.target["cfg(windows)"].dev_dependencies.contains_key("winapi"));
+    }
+}
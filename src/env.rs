@@ -1,7 +1,17 @@
 use crate::error::{Error, Result};
 use std::env;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(PartialEq, Debug)]
+/// Whether a boolean switch like `TRYBUILD_TRUNCATE=1` is turned on.
+pub fn enabled(var: &str) -> bool {
+    match env::var_os(var) {
+        Some(value) => !value.is_empty() && value != "0",
+        None => false,
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
 pub enum Update {
     Wip,
     Overwrite,
@@ -27,3 +37,84 @@ impl Update {
         }
     }
 }
+
+/// Seed for shuffling the order of tests, from `TRYBUILD_SEED`.
+///
+/// Either a number, to reproduce an earlier order, or `random`.
+pub fn seed() -> Result<Option<u64>> {
+    let var = match env::var_os("TRYBUILD_SEED") {
+        Some(var) => var,
+        None => return Ok(None),
+    };
+
+    match var.to_str() {
+        Some("random") => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            Ok(Some(now.as_secs() ^ u64::from(now.subsec_nanos())))
+        }
+        Some(seed) => match seed.parse() {
+            Ok(seed) => Ok(Some(seed)),
+            Err(_) => Err(Error::SeedVar(var)),
+        },
+        None => Err(Error::SeedVar(var)),
+    }
+}
+
+/// Where to write a SARIF report of the failed tests, from `TRYBUILD_SARIF`.
+pub fn sarif() -> Option<PathBuf> {
+    env::var_os("TRYBUILD_SARIF")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+// TRYBUILD_SHARD=k/n selects the k-th of n shards, counting from 1.
+pub fn shard() -> Result<Option<(usize, usize)>> {
+    let var = match env::var_os("TRYBUILD_SHARD") {
+        Some(var) => var,
+        None => return Ok(None),
+    };
+
+    let shard = var.to_str().and_then(|shard| {
+        let (k, n) = shard.split_once('/')?;
+        let (k, n) = (k.trim().parse().ok()?, n.trim().parse().ok()?);
+        if 1 <= k && k <= n {
+            Some((k, n))
+        } else {
+            None
+        }
+    });
+
+    match shard {
+        Some(shard) => Ok(Some(shard)),
+        None => Err(Error::ShardVar(var)),
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum DiffStyle {
+    Stacked,
+    Columns,
+}
+
+impl Default for DiffStyle {
+    fn default() -> Self {
+        DiffStyle::Stacked
+    }
+}
+
+impl DiffStyle {
+    pub fn env() -> Result<Self> {
+        let var = match env::var_os("TRYBUILD_DIFF") {
+            Some(var) => var,
+            None => return Ok(DiffStyle::default()),
+        };
+
+        match var.as_os_str().to_str() {
+            Some("stacked") => Ok(DiffStyle::Stacked),
+            Some("columns") => Ok(DiffStyle::Columns),
+            _ => Err(Error::DiffVar(var)),
+        }
+    }
+}
@@ -7,24 +7,50 @@ use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum Error {
+    BuildStd(String),
     Cargo(io::Error),
     CargoFail,
+    CargoConfig(toml::de::Error),
+    Config(PathBuf, toml::de::Error),
+    ContainsForbidden(String),
     BuildFail,
+    DiffVar(OsString),
+    DroppedFeatures(Vec<String>),
+    Edition(String),
+    EmptyStderr,
     Glob(GlobError),
     Io(io::Error),
-    Metadata(serde_json::Error),
+    IsDirectory(PathBuf),
+    Jobs,
+    KindFilter(String),
+    LacksRequired(String),
+    Metadata(serde_json::Error, String, String),
     Mismatch,
+    MismatchedCount(usize, usize),
+    Missing(PathBuf),
+    NotReproducible,
+    NotSilent,
     Open(PathBuf, io::Error),
+    Orphans(Vec<PathBuf>),
+    OutsidePath(PathBuf),
     Pattern(PatternError),
+    PermissionDenied(PathBuf),
     PkgName(env::VarError),
     ProjectDir,
     ReadStderr(io::Error),
     ReadStdout(io::Error),
     RunFailed,
+    Rustc(io::Error),
+    SeedVar(OsString),
+    ShardVar(OsString),
     ShouldNotHaveCompiled,
+    Signal(String),
+    Snapshot(PathBuf, String),
+    TestCount(usize, usize),
     TomlDe(toml::de::Error),
     TomlSer(toml::ser::Error),
     UpdateVar(OsString),
+    WorkspaceMember(PathBuf, String),
     WriteStderr(io::Error),
     External(String),
 }
@@ -36,23 +62,138 @@ impl Display for Error {
         use self::Error::*;
 
         match self {
+            BuildStd(version) => write!(
+                f,
+                "-Z build-std requires a nightly toolchain, but rustc is {}",
+                version,
+            ),
             Cargo(e) => write!(f, "failed to execute cargo: {}", e),
             CargoFail => write!(f, "cargo reported an error"),
+            CargoConfig(e) => write!(f, "invalid TestCases::cargo_config: {}", e),
+            Config(path, e) => write!(f, "invalid trybuild config {}: {}", path.display(), e),
+            ContainsForbidden(text) => {
+                write!(f, "compiler output contains forbidden text {:?}", text)
+            }
             BuildFail => write!(f, "failed to build"),
+            DiffVar(var) => write!(
+                f,
+                "unrecognized value of TRYBUILD_DIFF: {:?}",
+                var.to_string_lossy(),
+            ),
+            DroppedFeatures(features) => write!(
+                f,
+                "features enabled for cargo test are not declared in Cargo.toml: {}",
+                features.join(", "),
+            ),
+            Edition(edition) => write!(
+                f,
+                "unrecognized edition: {:?}, expected 2015, 2018, 2021 or 2024",
+                edition,
+            ),
+            EmptyStderr => {
+                write!(f, "test case failed to compile, but printed nothing to stderr")
+            }
             Glob(e) => write!(f, "{}", e),
             Io(e) => write!(f, "{}", e),
-            Metadata(e) => write!(f, "failed to read cargo metadata: {}", e),
+            IsDirectory(path) => write!(
+                f,
+                "{} is a directory; use a glob like `{}` for the tests in it",
+                path.display(),
+                path.join("*.rs").display(),
+            ),
+            Jobs => write!(f, "TestCases::jobs requires at least one job"),
+            KindFilter(kind) => write!(
+                f,
+                "unrecognized test kind in trybuild-kind={}, expected pass, compile_fail or output",
+                kind,
+            ),
+            LacksRequired(text) => {
+                write!(f, "compiler output does not contain required text {:?}", text)
+            }
+            Metadata(e, stdout, stderr) => {
+                write!(f, "failed to read cargo metadata: {}", e)?;
+                for (name, output) in &[("stdout", stdout), ("stderr", stderr)] {
+                    if !output.trim().is_empty() {
+                        write!(f, "\n\ncargo metadata {}:\n{}", name, output.trim_end())?;
+                    }
+                }
+                Ok(())
+            }
             Mismatch => write!(f, "compiler error does not match expected error"),
+            MismatchedCount(expected, actual) => write!(
+                f,
+                "expected test case to fail with {} error{}, but it failed with {}",
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                actual,
+            ),
+            Missing(path) if path.extension().is_none() => write!(
+                f,
+                "{} does not exist; test files need their `.rs` extension",
+                path.display(),
+            ),
+            Missing(path) => write!(f, "{} does not exist", path.display()),
+            NotReproducible => write!(f, "compiler output differs between two builds"),
+            NotSilent => write!(f, "expected test case to produce no output"),
             Open(path, e) => write!(f, "{}: {}", path.display(), e),
+            Orphans(files) => {
+                write!(f, "expected output files belong to no test:")?;
+                for file in files {
+                    write!(f, "\n    {}", file.display())?;
+                }
+                Ok(())
+            }
+            OutsidePath(path) => write!(
+                f,
+                "{} is outside of the crate under test; use \
+                 TestCases::allow_outside_paths if this is intentional",
+                path.display(),
+            ),
             Pattern(e) => write!(f, "{}", e),
-            PkgName(e) => write!(f, "failed to detect CARGO_PKG_NAME: {}", e),
-            ProjectDir => write!(f, "failed to determine name of project dir"),
+            PermissionDenied(path) => {
+                write!(f, "{} is not readable: permission denied", path.display())
+            }
+            PkgName(env::VarError::NotPresent) => write!(
+                f,
+                "CARGO_PKG_NAME is not set; trybuild tests need to be run through `cargo test`, \
+                 which sets it to the name of the crate under test, or be given \
+                 the name with TestCases::crate_name",
+            ),
+            PkgName(e) => write!(f, "failed to read CARGO_PKG_NAME: {}", e),
+            ProjectDir => write!(
+                f,
+                "CARGO_MANIFEST_DIR is not set; trybuild tests need to be run through `cargo test`, \
+                 which sets it to the directory of the crate under test, or be \
+                 given the directory with TestCases::manifest_dir",
+            ),
             ReadStderr(e) => write!(f, "failed to read stderr file: {}", e),
             ReadStdout(e) => write!(f, "failed to read stdout file: {}", e),
             RunFailed => write!(f, "execution of the test case was unsuccessful"),
+            Rustc(e) => write!(f, "failed to execute rustc: {}", e),
+            SeedVar(var) => write!(
+                f,
+                "unrecognized value of TRYBUILD_SEED: {:?}, expected a number or \"random\"",
+                var.to_string_lossy(),
+            ),
+            ShardVar(var) => write!(
+                f,
+                "unrecognized value of TRYBUILD_SHARD: {:?}, expected k/n with 1 <= k <= n",
+                var.to_string_lossy(),
+            ),
             ShouldNotHaveCompiled => {
                 write!(f, "expected test case to fail to compile, but it succeeded")
             }
+            Signal(signal) => write!(f, "the test case was killed by {}", signal),
+            Snapshot(path, e) => {
+                write!(f, "failed to update snapshot file {}: {}", path.display(), e)
+            }
+            TestCount(expected, actual) => write!(
+                f,
+                "expected {} test{}, but found {}",
+                expected,
+                if *expected == 1 { "" } else { "s" },
+                actual,
+            ),
             TomlDe(e) => write!(f, "{}", e),
             TomlSer(e) => write!(f, "{}", e),
             UpdateVar(var) => write!(
@@ -60,6 +201,12 @@ impl Display for Error {
                 "unrecognized value of TRYBUILD: {:?}",
                 var.to_string_lossy(),
             ),
+            WorkspaceMember(path, e) => write!(
+                f,
+                "failed to read the manifest of workspace member {}: {}",
+                path.display(),
+                e,
+            ),
             WriteStderr(e) => write!(f, "failed to write stderr file: {}", e),
             External(e) => write!(f, "{}", e),
         }
@@ -67,11 +214,20 @@ impl Display for Error {
 }
 
 impl Error {
+    #[allow(clippy::match_like_matches_macro)]
     pub fn already_printed(&self) -> bool {
         use self::Error::*;
 
         match self {
-            CargoFail | Mismatch | RunFailed | ShouldNotHaveCompiled => true,
+            CargoFail
+            | ContainsForbidden(_)
+            | LacksRequired(_)
+            | Mismatch
+            | NotReproducible
+            | NotSilent
+            | RunFailed
+            | ShouldNotHaveCompiled
+            | Signal(_) => true,
             _ => false,
         }
     }
@@ -1,13 +1,51 @@
 use serde::de::DeserializeOwned;
 use serde::{de, Deserialize, Deserializer};
+use std::collections::BTreeMap as Map;
 use std::env;
 use std::error::Error;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::path::PathBuf;
 
-pub fn find() -> Option<Vec<String>> {
-    try_find().ok()
+// Features enabled for the current compilation of the crate under test, out of
+// those `declared` in its manifest.
+//
+// Cargo's `CARGO_FEATURE_<NAME>` variables are consulted first; they are set
+// when the tests run from within a build script or a wrapper that forwards
+// them. Otherwise the feature selection is read from the fingerprint cargo
+// saved for the test binary.
+pub fn find(declared: &Map<String, Vec<String>>) -> Option<Vec<String>> {
+    from_env(env::vars_os(), declared).or_else(|| try_find().ok())
+}
+
+// The variable name is the feature name uppercased with `-` replaced by `_`,
+// so it is mapped back to the declared feature it came from.
+fn from_env<I>(vars: I, declared: &Map<String, Vec<String>>) -> Option<Vec<String>>
+where
+    I: IntoIterator<Item = (OsString, OsString)>,
+{
+    let mut found = false;
+    let mut features = Vec::new();
+    for (var, _value) in vars {
+        let var = match var.to_str().and_then(|var| var.strip_prefix("CARGO_FEATURE_")) {
+            Some(var) => var.to_owned(),
+            None => continue,
+        };
+        found = true;
+        for feature in declared.keys() {
+            if feature.to_uppercase().replace('-', "_") == var {
+                features.push(feature.clone());
+            }
+        }
+    }
+
+    if found {
+        features.sort();
+        features.dedup();
+        Some(features)
+    } else {
+        None
+    }
 }
 
 struct Ignored;
@@ -86,6 +124,7 @@ fn try_find() -> Result<Vec<String>, Ignored> {
     Ok(build.features)
 }
 
+#[allow(clippy::manual_range_contains)]
 fn is_lower_hex_digit(byte: u8) -> bool {
     byte >= b'0' && byte <= b'9' || byte >= b'a' && byte <= b'f'
 }
@@ -98,3 +137,26 @@ where
     let json = String::deserialize(deserializer)?;
     serde_json::from_str(&json).map_err(de::Error::custom)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_features() {
+        let declared: Map<String, Vec<String>> = ["default", "foo-bar", "baz"]
+            .iter()
+            .map(|f| (f.to_string(), Vec::new()))
+            .collect();
+
+        let vars = vec![
+            (OsString::from("CARGO_FEATURE_FOO_BAR"), OsString::from("1")),
+            (OsString::from("CARGO_PKG_NAME"), OsString::from("x")),
+            (OsString::from("CARGO_FEATURE_DEFAULT"), OsString::from("1")),
+        ];
+        assert_eq!(from_env(vars, &declared), Some(vec!["default".to_owned(), "foo-bar".to_owned()]));
+
+        let vars = vec![(OsString::from("CARGO_PKG_NAME"), OsString::from("x"))];
+        assert_eq!(from_env(vars, &declared), None);
+    }
+}
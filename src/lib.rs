@@ -1,4 +1,4 @@
-//! #### &emsp;A compiler diagnostics testing library in just 3 functions.
+//! #### &emsp;A compiler diagnostics testing library.
 //!
 //! Trybuild is a test harness for invoking rustc on a set of test cases and
 //! asserting that any resulting error messages are the ones intended.
@@ -37,6 +37,11 @@
 //! except with a different extension). If it matches, the test case is
 //! considered to succeed.
 //!
+//! Test files may be symlinks, for example to fixtures shared by several
+//! crates. The file that a link points to is what gets compiled, but the
+//! expected output is looked for next to the link, and diagnostics mention the
+//! link's path rather than the canonical one.
+//!
 //! Dependencies listed under `[dev-dependencies]` in the project's Cargo.toml
 //! are accessible from within the test cases.
 //!
@@ -95,7 +100,18 @@
 //!
 //! # Details
 //!
-//! That's the entire API.
+//! `new`, `compile_fail` and `pass` are all that most suites need. Everything
+//! else is optional configuration on [`TestCases`]: settings for particular
+//! tests, how compiler output is normalized before comparing, where expected
+//! output is kept, and how the test project is built. A suite can also be
+//! described in a file and loaded with [`TestCases::from_config`], and
+//! [`TestCases::discover`] lists the tests that a suite would run.
+//!
+//! The test project is built with the same features the crate under test was
+//! built with for `cargo test`. These are taken from the `CARGO_FEATURE_<NAME>`
+//! environment variables if any are set, the way cargo sets them for build
+//! scripts, and otherwise from the fingerprint cargo records for the test
+//! binary. Features not declared in the crate's manifest are ignored.
 //!
 //! <br>
 //!
@@ -120,6 +136,87 @@
 //! output directly in place. You'll want to check `git diff` afterward to be
 //! sure the compiler's output is what you had in mind.
 //!
+//! Parts of an expected output line that legitimately vary between runs can
+//! be replaced by `[...]` or `<IGNORE>`, which match any text within that
+//! line. Write `\[...]` for a literal `[...]`. Lines whose placeholders still
+//! match are kept as they are when overwriting.
+//!
+//! <br>
+//!
+//! # Environment variables
+//!
+//! Besides `TRYBUILD=overwrite`, these variables change how a run goes without
+//! touching the test code. Those that are flags take `1`.
+//!
+//! - `TRYBUILD_DIFF=columns` shows mismatches as expected and actual output
+//!   side by side rather than one above the other. The width is taken from the
+//!   `COLUMNS` environment variable; if it is not set, the usual stacked layout
+//!   is used.
+//!
+//! - `TRYBUILD_TRUNCATE` cuts compiler output lines wider than the terminal
+//!   short with an ellipsis, instead of wrapping them onto indented
+//!   continuation lines.
+//!
+//! - `TRYBUILD_ASCII` sticks to plain ASCII instead of box-drawing characters,
+//!   as is already done when the locale does not use UTF-8.
+//!
+//! - `TRYBUILD_SEED=random` runs the tests in a shuffled order, to catch tests
+//!   that only pass because of something an earlier test did. The seed is
+//!   printed so that the same order can be reproduced with
+//!   `TRYBUILD_SEED=<seed>`.
+//!
+//! - `TRYBUILD_TAP` prints the [Test Anything Protocol][tap] on stdout in place
+//!   of the usual report. The report for each failing test is included as its
+//!   diagnostic block.
+//!
+//! - `TRYBUILD_QUIET` leaves out the tests that pass. Failing tests are
+//!   reported in full, followed by a count of how many tests passed and failed.
+//!
+//! - `TRYBUILD_SARIF=path` also writes a [SARIF] report to that path, with a
+//!   result for each failed test located at the test file. The message of each
+//!   result is the report printed for that test, including the diff of a
+//!   mismatch. Every suite adds its results to the same report, replacing only
+//!   earlier results for the tests it ran.
+//!
+//! - `TRYBUILD_RERUN_FAILED` runs only the tests that failed in the last run.
+//!   Once they all pass, or if no run has failed yet, every test is run again.
+//!
+//! - `TRYBUILD_PRUNE`, after a run in which every test passed, deletes the
+//!   _*.stderr_ and _*.stdout_ files of the tests that were run which none of
+//!   them read, each with a note. Files in the directories of the tests that
+//!   belong to no test at all are only deleted with
+//!   [`TestCases::check_orphans`], since they may belong to another suite.
+//!   With `check_orphans`, unused files are reported without the variable,
+//!   but not deleted.
+//!
+//! - `TRYBUILD_SHARD=k/n` runs only the k-th of n roughly equal parts of the
+//!   tests, counting from 1, to split them between several machines.
+//!
+//! - `TRYBUILD_LIST` lists the tests without building or running any of them.
+//!   Each test is printed to stdout on a line of its own with its generated
+//!   name, kind and path, followed by each of its expected output files and
+//!   whether that file exists yet, separated by tabs. Paths that match no files
+//!   are reported as errors.
+//!
+//! - `TRYBUILD_VERBOSE` prints the features the tests are built with, and on a
+//!   mismatch, the raw output before normalization along with the diff against
+//!   each of the ways it is normalized rather than just the last. The number
+//!   of warnings in the build of each test is then also shown next to its
+//!   result.
+//!
+//! - `TRYBUILD_STREAM` shows cargo's output as it happens instead of capturing
+//!   it, to watch a slow test compile. The tests then only check whether each
+//!   case compiled; nothing is compared or written.
+//!
+//! - `RUST_BACKTRACE`, as with [`TestCases::backtrace`], keeps panic
+//!   backtraces in the output of run tests comparable across machines: frame
+//!   addresses, symbol hashes, thread ids and the `/rustc/<hash>/library/`
+//!   prefix of standard library locations are stripped or collapsed to
+//!   `$RUST/`.
+//!
+//! [tap]: https://testanything.org
+//! [SARIF]: https://sarifweb.azurewebsites.net
+//!
 //! <br>
 //!
 //! # What to test
@@ -207,29 +304,58 @@ mod term;
 mod path;
 
 mod cargo;
+mod config;
 mod dependencies;
 mod env;
 mod error;
 mod features;
-mod manifest;
+pub mod manifest;
 mod message;
 mod normalize;
+mod options;
 mod run;
 mod rustflags;
+mod sarif;
+mod snapshot;
+mod wildcard;
 
 use std::{fmt, thread};
 use std::cell::RefCell;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-use cargo::CargoRunner;
+use cargo::{CargoRunner, Project};
+use manifest::Manifest;
+use options::{Condition, ManifestEditor, Normalizer, Options};
 use std::process::Output;
 
+pub use normalize::Variations;
+pub use options::Environment;
+
 pub trait TestRunner {
     type Error: fmt::Display;
 
     fn prepare(&mut self, tests: &[Test]) -> Result<(), Self::Error>;
     fn build(&mut self, test: &Test) -> Result<Output, Self::Error>;
     fn run(&mut self, test: &Test) -> Result<Output, Self::Error>;
+
+    // Called in place of `prepare` with the project trybuild has already set
+    // up, which only the built-in runner makes use of.
+    #[doc(hidden)]
+    fn prepare_in(&mut self, project: &Project, tests: &[Test]) -> Result<(), Self::Error> {
+        let _ = project;
+        self.prepare(tests)
+    }
+
+    /// Whether the `actual` output matches the `expected` output.
+    ///
+    /// By default it does if any of the normalized forms of the output does,
+    /// allowing for wildcards in the expected output. Normalization and the
+    /// handling of missing or mismatched expected output are the same either
+    /// way.
+    fn compare(&self, expected: &str, actual: &Variations) -> bool {
+        actual.any(|actual| wildcard::matches(expected, actual))
+    }
 }
 
 #[derive(Debug)]
@@ -240,7 +366,10 @@ pub struct TestCases<R: TestRunner> {
 #[derive(Debug)]
 struct Runner<R: TestRunner> {
     tests: Vec<Test>,
+    options: Options,
     runner: R,
+    // The group that tests registered now belong to, while inside `group`.
+    group: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -248,6 +377,15 @@ pub struct Test {
     name: String,
     path: PathBuf,
     kind: TestKind,
+    inline: Option<Rc<Inline>>,
+    group: Option<String>,
+}
+
+// A test given as source code and expected stderr rather than as files.
+#[derive(Debug)]
+struct Inline {
+    source: String,
+    expected: String,
 }
 
 impl Test {
@@ -266,6 +404,11 @@ impl Test {
     pub fn kind(&self) -> TestKind {
         self.kind
     }
+
+    /// The name of the group the test was registered in, if any.
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -281,10 +424,40 @@ impl TestCases<CargoRunner> {
         TestCases {
             runner: RefCell::new(Runner {
                 tests: Vec::new(),
-                runner: CargoRunner::default()
+                options: Options::default(),
+                runner: CargoRunner::default(),
+                group: None,
             }),
         }
     }
+
+    /// Declares the test suite from a TOML file instead of builder calls.
+    ///
+    /// The keys are the names of the builder methods in kebab-case, and
+    /// per-test settings go in a table keyed by test path or glob:
+    ///
+    /// ```toml
+    /// compile-fail = ["tests/ui/fail/*.rs"]
+    /// pass = ["tests/ui/pass/*.rs"]
+    /// edition = "2021"
+    ///
+    /// [tests."tests/ui/fail/leak.rs"]
+    /// not-contains = ["InternalType"]
+    /// error-count = 1
+    /// ```
+    ///
+    /// The supported per-test settings are `flaky`, `unordered`, `silent`,
+    /// `error-count`, `rustflags`, `contains` and `not-contains`. Unknown keys
+    /// are an error. More builder methods may be called on the result.
+    pub fn from_config<P: AsRef<Path>>(path: P) -> TestCases<CargoRunner> {
+        let config = config::load(path.as_ref()).unwrap_or_else(|err| {
+            message::prepare_fail(err);
+            panic!("tests failed");
+        });
+        let t = TestCases::new();
+        config.apply(&t);
+        t
+    }
 }
 
 impl<R: TestRunner> TestCases<R> {
@@ -292,17 +465,23 @@ impl<R: TestRunner> TestCases<R> {
         TestCases {
             runner: RefCell::new(Runner {
                 tests: Vec::new(),
-                runner: runner
+                options: Options::default(),
+                runner,
+                group: None,
             }),
         }
     }
 
     fn push_test<P: AsRef<Path>>(&self, path: P, kind: TestKind) {
-        let num = self.runner.borrow().tests.len();
-        self.runner.borrow_mut().tests.push(Test {
+        let mut runner = self.runner.borrow_mut();
+        let num = runner.tests.len();
+        let group = runner.group.clone();
+        runner.tests.push(Test {
             name: Test::gen_name(num),
             path: path.as_ref().to_owned(),
             kind,
+            inline: None,
+            group,
         });
     }
 
@@ -314,9 +493,594 @@ impl<R: TestRunner> TestCases<R> {
         self.push_test(path, TestKind::CompileFail);
     }
 
+    /// Registers compile_fail tests that only run if `condition` holds, and
+    /// are otherwise reported as skipped.
+    ///
+    /// The condition is checked at run time and gets the [`Environment`] the
+    /// tests are built in, including the features enabled for them. The tests are
+    /// built for the same target as the test harness, so platform conditions
+    /// can use `cfg!`.
+    ///
+    /// ```no_run
+    /// let t = trybuild::TestCases::new();
+    /// t.compile_fail_if(|_| cfg!(unix), "tests/ui/unix/*.rs");
+    /// t.compile_fail_if(
+    ///     |env| env.features().into_iter().flatten().any(|f| f == "std"),
+    ///     "tests/ui/std/*.rs",
+    /// );
+    /// ```
+    pub fn compile_fail_if<F, P>(&self, condition: F, path: P)
+    where
+        F: Fn(&Environment) -> bool + 'static,
+        P: AsRef<Path>,
+    {
+        let condition = Condition(Rc::new(condition));
+        let key = path.as_ref().to_owned();
+        self.runner.borrow_mut().options.conditions.insert(key, condition);
+        self.push_test(path, TestKind::CompileFail);
+    }
+
+    /// Registers a compile_fail test for each of the given paths, for test
+    /// lists built at runtime. Each path is treated as if passed to
+    /// [`compile_fail`](Self::compile_fail) individually.
+    pub fn compile_fail_all<I>(&self, paths: I)
+    where
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        for path in paths {
+            self.push_test(path, TestKind::CompileFail);
+        }
+    }
+
+    /// Registers a compile_fail test from its source code and expected
+    /// stderr, with no files to keep next to it.
+    ///
+    /// The source is written to a file in the generated project, which
+    /// diagnostics refer to as `$INLINE`. The test is shown as `inline-N.rs`,
+    /// counting from 1, which is also the path to use for per-test settings.
+    /// Leading newlines and trailing whitespace of `expected` are ignored.
+    pub fn compile_fail_inline(&self, source: &str, expected: &str) {
+        let mut runner = self.runner.borrow_mut();
+        let num = runner.tests.len();
+        let inline_num = runner.tests.iter().filter(|test| test.inline.is_some()).count() + 1;
+        let group = runner.group.clone();
+        runner.tests.push(Test {
+            name: Test::gen_name(num),
+            path: PathBuf::from(format!("inline-{}.rs", inline_num)),
+            kind: TestKind::CompileFail,
+            inline: Some(Rc::new(Inline {
+                source: source.to_owned(),
+                expected: expected.to_owned(),
+            })),
+            group,
+        });
+    }
+
     pub fn output<P: AsRef<Path>>(&self, path: P) {
         self.push_test(path, TestKind::Output);
     }
+
+    /// Puts the tests registered within `f` in a group called `name`.
+    ///
+    /// A header with the name is printed before the results of each group,
+    /// and the pass and fail counts of each group are printed at the end.
+    ///
+    /// ```no_run
+    /// let t = trybuild::TestCases::new();
+    /// t.group("type errors", |g| {
+    ///     g.compile_fail("tests/ui/types/*.rs");
+    /// });
+    /// ```
+    pub fn group<F: FnOnce(&Self)>(&self, name: &str, f: F) {
+        let outer = self.runner.borrow_mut().group.replace(name.to_owned());
+        f(self);
+        self.runner.borrow_mut().group = outer;
+    }
+
+    /// Registers a final normalization step for compiler and program output.
+    ///
+    /// The closure receives the output after trybuild's own normalizations
+    /// and returns the text that is compared against and written to the
+    /// expected files, in place of any other normalized form.
+    pub fn normalize_with<F>(&self, normalize: F)
+    where
+        F: Fn(&str) -> String + 'static,
+    {
+        self.runner.borrow_mut().options.normalizer = Some(Normalizer(Rc::new(normalize)));
+    }
+
+    /// Changes the Cargo.toml of the generated test project before it is
+    /// written, for settings that trybuild has no option for.
+    ///
+    /// ```
+    /// use trybuild::manifest::Value;
+    ///
+    /// # let t = trybuild::TestCases::new();
+    /// t.edit_manifest(|manifest| {
+    ///     let metadata: Value = "docs = false".parse().unwrap();
+    ///     manifest.package.rest.insert("metadata".to_owned(), metadata);
+    /// });
+    /// ```
+    ///
+    /// Nothing checks the result, so an edit that leaves the manifest invalid
+    /// or removes what trybuild relies on, like the dependency on the crate
+    /// under test or the `[[bin]]` of each test, breaks the build of every
+    /// test.
+    pub fn edit_manifest<F>(&self, edit: F)
+    where
+        F: Fn(&mut Manifest) + 'static,
+    {
+        self.runner.borrow_mut().options.edit_manifest = Some(ManifestEditor(Rc::new(edit)));
+    }
+
+    /// Keeps all expected output in one TOML or JSON file instead of in
+    /// `*.stderr` and `*.stdout` files next to each test.
+    ///
+    /// The file maps the path that each standalone expected file would have
+    /// had, relative to the crate, like `"tests/ui/missing-repr.stderr"`, to
+    /// its contents. Files outside the crate are keyed relative to
+    /// [`base_dir`](Self::base_dir) instead. A path
+    /// ending in `.json` selects JSON; anything else is read as TOML.
+    pub fn snapshot_file<P: AsRef<Path>>(&self, path: P) {
+        self.runner.borrow_mut().options.snapshot_file = Some(path.as_ref().to_owned());
+    }
+
+    /// Stores expected output in `.snap` files under a `snapshots` directory,
+    /// each starting with a short header naming the test it belongs to.
+    ///
+    /// The expected output for `tests/ui/foo.rs` lives in
+    /// `tests/ui/snapshots/foo.stderr.snap`. New or changed output is written
+    /// next to it as a pending `.snap.new` file, to be reviewed and renamed
+    /// over the `.snap` file.
+    #[cfg(feature = "snap")]
+    pub fn snap_files(&self) {
+        self.runner.borrow_mut().options.snap_files = true;
+    }
+
+    /// Writes expected output compressed, as `tests/ui/foo.stderr.gz` and so
+    /// on, when overwriting it or saving it to the *wip* directory.
+    ///
+    /// Compressed expected files are read whether or not this is set, as long
+    /// as there is no uncompressed file alongside.
+    #[cfg(feature = "gzip")]
+    pub fn gzip(&self) {
+        self.runner.borrow_mut().options.gzip = true;
+    }
+
+    /// Makes trailing whitespace on each line of output significant.
+    ///
+    /// By default it is trimmed before comparing. In strict mode it must
+    /// match, and is made visible as `·` in the diff of a mismatch.
+    pub fn strict_whitespace(&self) {
+        self.runner.borrow_mut().options.strict_whitespace = true;
+    }
+
+    /// Normalizes the locations that diagnostics give for code generated by
+    /// procedural macros, which vary between compiler versions.
+    ///
+    /// Locations like `--> <proc-macro>:1:5` become `--> $PROC_MACRO`, and the
+    /// underline of an `in this derive macro expansion` label is dropped.
+    pub fn normalize_proc_macro_paths(&self) {
+        self.runner.borrow_mut().options.proc_macro_paths = true;
+    }
+
+    /// Writes the least normalized form of the output as the expected output,
+    /// instead of the most normalized one.
+    ///
+    /// This makes the saved snapshots as strict as possible, at the cost of
+    /// them being more likely to change between compiler versions. Matching
+    /// still accepts any of the normalized forms.
+    pub fn prefer_strict(&self) {
+        self.runner.borrow_mut().options.prefer_strict = true;
+    }
+
+    /// Builds the standard library crates from source for the tests, as with
+    /// `cargo build -Z build-std=core,alloc`. Requires a nightly toolchain.
+    pub fn build_std(&self, crates: &[&str]) {
+        let crates = crates.iter().map(|&krate| krate.to_owned());
+        self.runner.borrow_mut().options.build_std.extend(crates);
+    }
+
+    /// Passes extra arguments to every cargo command that builds or runs the
+    /// tests, after the arguments trybuild itself passes.
+    ///
+    /// This is an escape hatch for cargo flags that trybuild has no dedicated
+    /// option for, like `&["--config", "net.retry=0"]`. Arguments that change
+    /// what cargo builds or where it puts the output can break trybuild.
+    pub fn cargo_args(&self, args: &[&str]) {
+        let args = args.iter().map(|&arg| arg.to_owned());
+        self.runner.borrow_mut().options.cargo_args.extend(args);
+    }
+
+    /// Adds a fragment of TOML to the `.cargo/config` of the generated test
+    /// project, for settings like registry mirrors or target runners.
+    ///
+    /// ```no_run
+    /// # let t = trybuild::TestCases::new();
+    /// t.cargo_config(r#"
+    ///     [source.crates-io]
+    ///     replace-with = "mirror"
+    ///
+    ///     [source.mirror]
+    ///     registry = "sparse+https://mirror.example.com/index/"
+    /// "#);
+    /// ```
+    ///
+    /// Tables are merged with the config trybuild writes key by key, and with
+    /// the fragments of earlier calls, so each value is taken from the last
+    /// place that sets it. This includes `build.rustflags`, which trybuild
+    /// sets to `["-A", "dead_code"]` and a fragment may replace. As usual,
+    /// `RUSTFLAGS` in the environment takes precedence over `build.rustflags`.
+    pub fn cargo_config(&self, toml: &str) {
+        self.runner.borrow_mut().options.cargo_config.push(toml.to_owned());
+    }
+
+    /// Compiles the tests through a wrapper like `sccache`.
+    ///
+    /// `RUSTC_WRAPPER` and `RUSTC_WORKSPACE_WRAPPER` from the environment are
+    /// inherited by cargo already, but a wrapper configured in the crate's
+    /// `.cargo/config.toml` does not reach the generated test project, which
+    /// lives in the target directory. This sets `RUSTC_WRAPPER` explicitly.
+    pub fn rustc_wrapper<P: AsRef<Path>>(&self, wrapper: P) {
+        self.runner.borrow_mut().options.rustc_wrapper = Some(wrapper.as_ref().to_owned());
+    }
+
+    /// Builds and runs the tests with the cargo binary at `path` rather than
+    /// the one running the tests, or `cargo` from the `PATH`.
+    pub fn cargo_bin<P: AsRef<Path>>(&self, path: P) {
+        self.runner.borrow_mut().options.cargo_bin = Some(path.as_ref().to_owned());
+    }
+
+    /// Turns incremental compilation of the tests on or off by setting
+    /// `CARGO_INCREMENTAL`.
+    ///
+    /// Incremental compilation can occasionally affect diagnostics, so turning
+    /// it off makes the expected output reproducible between local builds and
+    /// CI, where it is usually off already. Without this, cargo's own default
+    /// applies.
+    pub fn incremental(&self, enabled: bool) {
+        self.runner.borrow_mut().options.incremental = Some(enabled);
+    }
+
+    /// Limits cargo to `jobs` parallel jobs when building the tests, as with
+    /// `CARGO_BUILD_JOBS`.
+    ///
+    /// With a single job, codegen within each crate is not split up either,
+    /// for output that is as deterministic as possible at the cost of speed.
+    /// Zero jobs is reported as an error when the tests are run.
+    pub fn jobs(&self, jobs: usize) {
+        self.runner.borrow_mut().options.jobs = Some(jobs);
+    }
+
+    /// Reruns the tests at `path`, which may be a glob, up to `retries` more
+    /// times if they fail at runtime. The build is not retried.
+    ///
+    /// This is meant for pass and output tests that are occasionally flaky
+    /// due to timing.
+    pub fn flaky<P: AsRef<Path>>(&self, path: P, retries: usize) {
+        let path = path.as_ref().to_owned();
+        self.runner.borrow_mut().options.flaky.insert(path, retries);
+    }
+
+    /// Compares the output of the tests at `path`, which may be a glob,
+    /// without regard to the order of its lines.
+    ///
+    /// Both the expected and the actual output are sorted line by line before
+    /// comparing, and are shown sorted in the diff of a mismatch.
+    pub fn unordered<P: AsRef<Path>>(&self, path: P) {
+        let path = path.as_ref().to_owned();
+        self.runner.borrow_mut().options.unordered.insert(path, ());
+    }
+
+    /// Passes additional `flags` to rustc when building and running the tests
+    /// at `path`, which may be a glob.
+    ///
+    /// ```
+    /// # let t = trybuild::TestCases::new();
+    /// t.rustflags("tests/ui/overflow.rs", &["-C", "overflow-checks=on"]);
+    /// ```
+    ///
+    /// The flags are given to cargo through `RUSTFLAGS`, after any that are
+    /// set in the environment already and the `-A dead_code` that trybuild
+    /// always passes. Changing `RUSTFLAGS` makes cargo rebuild the
+    /// dependencies of the test, so this is slower than a flag for every test.
+    pub fn rustflags<P: AsRef<Path>>(&self, path: P, flags: &[&str]) {
+        let path = path.as_ref().to_owned();
+        let flags = flags.iter().map(|&flag| flag.to_owned()).collect();
+        self.runner.borrow_mut().options.rustflags.insert(path, flags);
+    }
+
+    /// Checks that the compile_fail tests at `path`, which may be a glob,
+    /// report exactly `count` errors, in addition to matching their expected
+    /// output.
+    ///
+    /// This catches several errors being collapsed into one even when the
+    /// expected output is matched loosely with wildcards.
+    pub fn compile_fail_error_count<P: AsRef<Path>>(&self, path: P, count: usize) {
+        let path = path.as_ref().to_owned();
+        self.runner.borrow_mut().options.error_count.insert(path, count);
+    }
+
+    /// Requires the normalized stderr of the compile_fail tests at `path`,
+    /// which may be a glob, to contain `text`, such as an error code.
+    ///
+    /// This is checked in addition to the expected output, and may be called
+    /// several times for the same tests.
+    pub fn compile_fail_contains<P: AsRef<Path>>(&self, path: P, text: &str) {
+        let path = path.as_ref().to_owned();
+        let mut runner = self.runner.borrow_mut();
+        let required = runner.options.contains.entry(path).or_default();
+        required.push(text.to_owned());
+    }
+
+    /// Fails the compile_fail tests at `path`, which may be a glob, if their
+    /// normalized stderr contains `text`, such as the name of an internal type
+    /// that should not leak into diagnostics.
+    ///
+    /// This is checked in addition to the expected output, and may be called
+    /// several times for the same tests.
+    pub fn compile_fail_not_contains<P: AsRef<Path>>(&self, path: P, text: &str) {
+        let path = path.as_ref().to_owned();
+        let mut runner = self.runner.borrow_mut();
+        let forbidden = runner.options.not_contains.entry(path).or_default();
+        forbidden.push(text.to_owned());
+    }
+
+    /// Attaches a short description of what the tests at `path`, which may be
+    /// a glob, check. It is shown after the name of the test when it runs.
+    pub fn describe<P: AsRef<Path>>(&self, path: P, description: &str) {
+        let path = path.as_ref().to_owned();
+        let description = description.to_owned();
+        self.runner.borrow_mut().options.descriptions.insert(path, description);
+    }
+
+    /// Fails the pass tests at `path`, which may be a glob, if they print
+    /// anything at all to stdout or stderr when run.
+    pub fn silent<P: AsRef<Path>>(&self, path: P) {
+        let path = path.as_ref().to_owned();
+        self.runner.borrow_mut().options.silent.insert(path, ());
+    }
+
+    /// Runs the test binaries with `RUST_BACKTRACE=1`, so that output tests
+    /// can assert on the backtrace of a panic.
+    ///
+    /// Backtraces are normalized to be stable across machines. Without this,
+    /// `RUST_BACKTRACE` is inherited from the environment as usual.
+    pub fn backtrace(&self, enabled: bool) {
+        self.runner.borrow_mut().options.backtrace = enabled;
+    }
+
+    /// Builds the tests with every feature of the crate under test enabled,
+    /// rather than the features it was built with for `cargo test`.
+    pub fn all_features(&self) {
+        self.runner.borrow_mut().options.all_features = true;
+    }
+
+    /// Leaves the default features of the crate under test enabled in
+    /// addition to the features it was built with for `cargo test`.
+    ///
+    /// By default the tests are built with `--no-default-features` and only
+    /// the features that were actually enabled.
+    pub fn keep_default_features(&self) {
+        self.runner.borrow_mut().options.keep_default_features = true;
+    }
+
+    /// Fails instead of warning when a feature enabled for `cargo test` is
+    /// not declared in the crate's manifest and so cannot be enabled for the
+    /// tests.
+    pub fn strict_features(&self) {
+        self.runner.borrow_mut().options.strict_features = true;
+    }
+
+    /// Prints at most `lines` lines of any one block of output or diff,
+    /// followed by a count of the lines left out.
+    ///
+    /// This only limits what is shown; the output is still compared and
+    /// written in full.
+    pub fn max_output_lines(&self, lines: usize) {
+        self.runner.borrow_mut().options.max_output_lines = Some(lines);
+    }
+
+    /// Fails compile-fail tests that fail to compile without printing anything
+    /// to stderr, which usually means they failed for some unrelated reason.
+    pub fn require_stderr(&self) {
+        self.runner.borrow_mut().options.require_stderr = true;
+    }
+
+    /// Always enables the given features of the crate under test, in addition
+    /// to those it was built with for `cargo test`.
+    pub fn self_features(&self, features: &[&str]) {
+        let features = features.iter().map(|&feature| feature.to_owned());
+        self.runner.borrow_mut().options.self_features.extend(features);
+    }
+
+    /// Makes the crate in the directory `path`, relative to the crate under
+    /// test, available to the tests as a dependency, like a sibling member
+    /// of the same workspace: `t.workspace_member("../macros")`.
+    ///
+    /// The dependency is named after the crate, with its default features,
+    /// and takes the place of any dependency of the same name.
+    pub fn workspace_member<P: AsRef<Path>>(&self, path: P) {
+        let path = path.as_ref().to_owned();
+        self.runner.borrow_mut().options.workspace_members.push(path);
+    }
+
+    /// Builds the tests with the given edition, like `"2024"`, rather than
+    /// the edition of the crate under test.
+    pub fn edition(&self, edition: &str) {
+        self.runner.borrow_mut().options.edition = Some(edition.to_owned());
+    }
+
+    /// Code such as `use` statements or helper macros to put in front of
+    /// every test.
+    ///
+    /// Each test is built from a generated file holding the prelude followed
+    /// by an `include!` of the test, so diagnostics about the test itself
+    /// still point at its own lines. Diagnostics about the prelude refer to it
+    /// as `$PRELUDE`. Since test files are included, they cannot have inner
+    /// attributes like `#![allow(...)]`.
+    pub fn prelude(&self, code: &str) {
+        self.runner.borrow_mut().options.prelude = Some(code.to_owned());
+    }
+
+    /// Sets the name of the crate under test, which is otherwise taken from
+    /// the `CARGO_PKG_NAME` that `cargo test` sets.
+    pub fn crate_name(&self, name: &str) {
+        self.runner.borrow_mut().options.crate_name = Some(name.to_owned());
+    }
+
+    /// Sets the directory containing the Cargo.toml of the crate under test,
+    /// which is otherwise taken from the `CARGO_MANIFEST_DIR` that
+    /// `cargo test` sets.
+    ///
+    /// Together with [`crate_name`](Self::crate_name), this allows running the
+    /// tests from outside of `cargo test`.
+    pub fn manifest_dir<P: AsRef<Path>>(&self, dir: P) {
+        self.runner.borrow_mut().options.manifest_dir = Some(dir.as_ref().to_owned());
+    }
+
+    /// Adds a lossier level of normalization of compiler and program output.
+    ///
+    /// The closure receives the most normalized form of the output so far.
+    /// The output matches the expected output if it does at any level, and
+    /// the last level added is what gets written to expected files, unless a
+    /// [`normalize_with`](Self::normalize_with) step transforms it further.
+    pub fn add_normalization<F>(&self, normalize: F)
+    where
+        F: Fn(&str) -> String + 'static,
+    {
+        let normalization = Normalizer(Rc::new(normalize));
+        self.runner.borrow_mut().options.normalizations.push(normalization);
+    }
+
+    /// Sets the extension of the files holding the expected stderr of tests,
+    /// like `"expected"` for `tests/ui/foo.expected`. The default is
+    /// `"stderr"`.
+    pub fn stderr_ext(&self, ext: &str) {
+        let ext = ext.trim_start_matches('.').to_owned();
+        self.runner.borrow_mut().options.stderr_ext = Some(ext);
+    }
+
+    /// Keeps the expected output of the tests under `tests` in a separate
+    /// tree under `expected`, at the same relative paths, instead of next to
+    /// each test.
+    ///
+    /// For example with `expected_dir("tests/ui", "tests/ui-expected")`, the
+    /// expected output of `tests/ui/parse/bad.rs` is in
+    /// `tests/ui-expected/parse/bad.stderr`. Expected files that are still
+    /// next to their test are used, and updated, where they are.
+    pub fn expected_dir<P: AsRef<Path>, Q: AsRef<Path>>(&self, tests: P, expected: Q) {
+        let dirs = (tests.as_ref().to_owned(), expected.as_ref().to_owned());
+        self.runner.borrow_mut().options.expected_dir = Some(dirs);
+    }
+
+    /// Resolves relative test paths and globs, including those given to other
+    /// per-test settings, against `dir` rather than the working directory.
+    ///
+    /// The expected output files are looked for next to the resolved paths.
+    pub fn base_dir<P: AsRef<Path>>(&self, dir: P) {
+        self.runner.borrow_mut().options.base_dir = Some(dir.as_ref().to_owned());
+    }
+
+    /// Allows test paths that lead outside of the crate under test, such as
+    /// `../other-crate/tests/ui/*.rs`, which are otherwise reported as errors.
+    pub fn allow_outside_paths(&self) {
+        self.runner.borrow_mut().options.allow_outside_paths = true;
+    }
+
+    /// Checks all the compile_fail tests with a single cargo invocation before
+    /// any of them are compared, rather than invoking cargo once per test.
+    ///
+    /// This saves the startup and dependency resolution of cargo for every
+    /// test, which adds up for large suites. Tests with their own
+    /// [`rustflags`](Self::rustflags) are still built one at a time, as is any
+    /// test that turns out to compile. Requires cargo 1.74 or newer; with
+    /// older versions every test is built on its own as usual.
+    ///
+    /// Has no effect together with
+    /// [`check_reproducible`](Self::check_reproducible), which needs both
+    /// builds of a test to be made the same way.
+    pub fn batch_compile_fail(&self) {
+        self.runner.borrow_mut().options.batch_compile_fail = true;
+    }
+
+    /// Builds each compile_fail test twice and fails it if the normalized
+    /// compiler output is not the same both times.
+    ///
+    /// This finds diagnostics that vary from one build to the next, and
+    /// normalizations that miss something which does. It doubles the time
+    /// spent building the tests.
+    pub fn check_reproducible(&self) {
+        self.runner.borrow_mut().options.check_reproducible = true;
+    }
+
+    /// Compares the warnings of compile_fail tests against a separate
+    /// _*.warnings_ file next to the test, leaving only the errors in the
+    /// _*.stderr_ file.
+    ///
+    /// A missing _*.warnings_ file is treated like one with no warnings, so
+    /// tests that do not warn need not have one.
+    pub fn separate_warnings(&self) {
+        self.runner.borrow_mut().options.separate_warnings = true;
+    }
+
+    /// Keeps the output that the previous run wrote to the _wip_ directory for
+    /// each test, as _wip/*.stderr.prev_ and so on, instead of replacing it.
+    ///
+    /// This makes it easy to diff successive attempts while working on a
+    /// diagnostic. It does not apply with a snapshot file.
+    pub fn keep_previous_wip(&self) {
+        self.runner.borrow_mut().options.keep_previous_wip = true;
+    }
+
+    /// Starts each expected output file that trybuild writes with a line
+    /// recording the versions of trybuild and rustc that produced it, like
+    /// `// trybuild 1.0.11, rustc 1.70.0 (90c541806 2023-05-31)`.
+    ///
+    /// Such a first line is skipped when comparing, whether or not this is
+    /// set. It is not written to a snapshot file.
+    pub fn snapshot_header(&self) {
+        self.runner.borrow_mut().options.snapshot_header = true;
+    }
+
+    /// Fails the run unless exactly `count` tests are registered, counting
+    /// every file matched by a glob but before any command-line filter.
+    ///
+    /// This catches tests that silently stop running because their files
+    /// were moved out from under a glob.
+    pub fn expect_test_count(&self, count: usize) {
+        self.runner.borrow_mut().options.expected_count = Some(count);
+    }
+
+    /// Fails the run if a directory of the tests holds a _*.stderr_ or
+    /// _*.stdout_ file that belongs to none of them, such as one left behind
+    /// after its test was deleted.
+    ///
+    /// Every test in such a directory needs to be registered with the same
+    /// `TestCases` for this to work.
+    ///
+    /// After a run in which every test passed, expected files that none of the
+    /// tests read are reported too. With `TRYBUILD_PRUNE=1`, these and the
+    /// orphans are deleted after the run instead.
+    pub fn check_orphans(&self) {
+        self.runner.borrow_mut().options.check_orphans = true;
+    }
+
+    /// Returns the tests that would be run, in the order they would run in,
+    /// after glob expansion and after applying any `trybuild=` or
+    /// `trybuild-kind=` filter from the command line, `TRYBUILD_SHARD`,
+    /// `TRYBUILD_SEED` and `TRYBUILD_RERUN_FAILED`. Nothing is printed.
+    ///
+    /// Paths that fail to expand, which a run reports as failed tests, are
+    /// skipped, as are globs that match no files. Tests registered with
+    /// [`compile_fail_if`](Self::compile_fail_if) are included whether or not
+    /// their condition holds. If the filters or environment variables are
+    /// invalid, so that a run would fail before running anything, no tests are
+    /// returned.
+    pub fn discover(&self) -> Vec<Test> {
+        self.runner.borrow().discover()
+    }
 }
 
 #[doc(hidden)]
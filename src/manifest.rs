@@ -1,8 +1,12 @@
-use crate::dependencies::{Dependency, Patch, RegistryPatch};
+//! The Cargo.toml generated for the project that the tests are built in, as
+//! seen by [`TestCases::edit_manifest`](crate::TestCases::edit_manifest).
+
+pub use crate::dependencies::{Dependency, Patch, RegistryPatch};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap as Map;
 use std::ffi::OsStr;
 use std::path::PathBuf;
+pub use toml::Value;
 
 #[derive(Serialize, Debug)]
 pub struct Manifest {
@@ -10,6 +14,10 @@ pub struct Manifest {
     #[serde(skip_serializing_if = "Map::is_empty")]
     pub features: Map<String, Vec<String>>,
     pub dependencies: Map<String, Dependency>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub target: Map<String, Target>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub lints: Map<String, Value>,
     #[serde(rename = "bin")]
     pub bins: Vec<Bin>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -18,6 +26,14 @@ pub struct Manifest {
     pub patch: Map<String, RegistryPatch>,
     #[serde(skip_serializing_if = "Map::is_empty")]
     pub replace: Map<String, Patch>,
+    /// Any other tables, written as is.
+    #[serde(flatten)]
+    pub rest: Map<String, Value>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Target {
+    pub dependencies: Map<String, Dependency>,
 }
 
 #[derive(Serialize, Debug)]
@@ -26,6 +42,9 @@ pub struct Package {
     pub version: String,
     pub edition: Edition,
     pub publish: bool,
+    /// Any other keys, such as `metadata`, written as is.
+    #[serde(flatten)]
+    pub rest: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -34,6 +53,22 @@ pub enum Edition {
     E2015,
     #[serde(rename = "2018")]
     E2018,
+    #[serde(rename = "2021")]
+    E2021,
+    #[serde(rename = "2024")]
+    E2024,
+}
+
+impl Edition {
+    pub(crate) fn parse(edition: &str) -> Option<Edition> {
+        match edition {
+            "2015" => Some(Edition::E2015),
+            "2018" => Some(Edition::E2018),
+            "2021" => Some(Edition::E2021),
+            "2024" => Some(Edition::E2024),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -62,7 +97,10 @@ pub struct Build {
 }
 
 #[derive(Serialize, Debug)]
-pub struct Workspace {}
+pub struct Workspace {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolver: Option<String>,
+}
 
 impl Default for Edition {
     fn default() -> Self {
@@ -1,12 +1,17 @@
 use termcolor::Color::{self, *};
 
 use super::{TestKind, Test};
+use crate::cargo::Project;
+use crate::env::{self, DiffStyle};
 use crate::error::Error;
 use crate::normalize;
+use crate::options::Options;
+use crate::snapshot;
 use crate::term;
 
+use std::borrow::Cow;
 use std::path::Path;
-use std::process::Output;
+use std::process::{ExitStatus, Output};
 
 pub(crate) enum Level {
     Fail,
@@ -40,33 +45,262 @@ pub(crate) fn test_fail(err: Error) {
     println!();
 }
 
+pub(crate) fn tap_plan(count: usize) {
+    tap_line(&format!("1..{}", count));
+}
+
+// Reports one test in TAP format. Whatever trybuild would have printed about
+// the test is attached to failures as a YAML diagnostic block.
+pub(crate) fn tap_result(number: usize, test: &Test, description: Option<&str>, ok: bool) {
+    let details = term::take_captured();
+    let status = if ok { "ok" } else { "not ok" };
+    let mut line = format!("{} {} - {}", status, number, test.path.display());
+    if let Some(description) = description {
+        line += &format!(" ({})", description);
+    }
+    tap_line(&line);
+
+    if !ok && !details.trim().is_empty() {
+        tap_line("  ---");
+        tap_line("  message: |");
+        for line in details.trim_end().lines() {
+            tap_line(&format!("    {}", line));
+        }
+        tap_line("  ...");
+    }
+}
+
+pub(crate) fn tap_skipped(number: usize, test: &Test) {
+    term::take_captured();
+    tap_line(&format!("ok {} - {} # SKIP", number, test.path.display()));
+}
+
+pub(crate) fn tap_bail_out() {
+    let details = term::take_captured();
+    let reason = details.lines().find(|line| !line.trim().is_empty());
+    tap_line(&format!("Bail out! {}", reason.unwrap_or("").trim()));
+}
+
+// TAP goes to stdout, where TAP consumers expect it, and bypasses the terminal
+// which is capturing the human-readable output.
+fn tap_line(line: &str) {
+    use std::io::Write;
+    let _ = writeln!(std::io::stdout(), "{}", line);
+}
+
+// One tab-separated line per test: its name, kind, path, and each file holding
+// its expected output along with whether that exists yet. The list goes to
+// stdout, apart from everything else trybuild prints, so that it can be piped
+// into other tools; paths that failed to expand are reported as errors.
+pub(crate) fn list(tests: &[Test], bad_tests: &[(Test, Error)], options: &Options) {
+    use std::io::Write;
+
+    for (test, err) in bad_tests {
+        term::bold_color(Red);
+        print!("ERROR");
+        term::reset();
+        println!(": {}: {}", test.path.display(), err);
+    }
+
+    for test in tests {
+        let (kind, expected) = match test.kind {
+            TestKind::Pass => ("pass", vec![]),
+            TestKind::CompileFail => ("compile_fail", vec![test.stderr_path(options)]),
+            TestKind::Output => (
+                "output",
+                vec![test.stderr_path(options), test.path.with_extension("stdout")],
+            ),
+        };
+        let mut line = format!("{}\t{}\t{}", test.name, kind, test.path.display());
+        if expected.is_empty() {
+            line += "\t-\t-";
+        }
+        for path in &expected {
+            let exists = if snapshot::exists(options, path) { "exists" } else { "missing" };
+            line += &format!("\t{}\t{}", path.display(), exists);
+        }
+        let _ = writeln!(std::io::stdout(), "{}", line);
+    }
+}
+
 pub(crate) fn no_tests_enabled() {
     term::color(Yellow);
     println!("There are no tests enabled yet.");
     term::reset();
 }
 
+pub(crate) fn shuffled(seed: u64) {
+    term::color(Yellow);
+    println!("Running tests in shuffled order; rerun with TRYBUILD_SEED={} to reproduce.", seed);
+    term::reset();
+    println!();
+}
+
+pub(crate) fn features(project: &Project) {
+    let features = if project.options.all_features {
+        "all".to_owned()
+    } else {
+        match &project.features {
+            Some(features) if features.is_empty() => "none".to_owned(),
+            Some(features) => features.join(", "),
+            None => "unknown, using cargo's defaults".to_owned(),
+        }
+    };
+
+    term::bold();
+    print!("Features");
+    term::reset();
+    println!(": {}", features);
+    println!();
+}
+
+pub(crate) fn dropped_features(features: &[String]) {
+    term::bold_color(Yellow);
+    print!("WARNING");
+    term::reset();
+    println!(
+        ": features enabled for cargo test are not declared in Cargo.toml and will not be enabled for the tests: {}",
+        features.join(", "),
+    );
+    println!();
+}
+
+pub(crate) fn empty_glob(pattern: &str) {
+    term::bold_color(Yellow);
+    print!("WARNING");
+    term::reset();
+    println!(": no test files match {}", pattern);
+    println!();
+}
+
+pub(crate) fn unused(project: &Project, path: &Path) {
+    term::bold_color(Yellow);
+    print!("WARNING");
+    term::reset();
+    println!(
+        ": {} is not used by any test; rerun with TRYBUILD_PRUNE=1 to delete it",
+        display_path(project, path),
+    );
+}
+
+pub(crate) fn pruned(project: &Project, path: &Path) {
+    term::color(Yellow);
+    println!("Deleted unused {}", display_path(project, path));
+    term::reset();
+}
+
+pub(crate) fn rerun_failed(count: usize) {
+    term::color(Yellow);
+    let tests = if count == 1 { "test" } else { "tests" };
+    println!("Rerunning the {} {} that failed last time.", count, tests);
+    term::reset();
+    println!();
+}
+
+pub(crate) fn shard(shard: (usize, usize)) {
+    term::color(Yellow);
+    println!("Running shard {}/{} of the tests.", shard.0, shard.1);
+    term::reset();
+    println!();
+}
+
+pub(crate) fn no_tests_matched(filters: &[String]) {
+    let filters = filters
+        .iter()
+        .map(|filter| format!("'{}'", filter))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    term::color(Yellow);
+    println!("There are no tests matching filter {}.", filters);
+    term::reset();
+}
+
+pub(crate) fn summary(total: usize, failures: usize, skipped: usize) {
+    let color = if failures == 0 { Green } else { Red };
+    term::bold_color(color);
+    print!("{} of {} tests passed", total - failures - skipped, total);
+    term::reset();
+    if failures > 0 {
+        print!(", {} failed", failures);
+    }
+    if skipped > 0 {
+        print!(", {} skipped", skipped);
+    }
+    println!();
+    println!();
+}
+
+pub(crate) fn group(name: &str) {
+    term::bold();
+    println!("{}:", name);
+    term::reset();
+}
+
+// The passed and failed counts of each group of tests.
+pub(crate) fn group_summary(groups: &[(String, usize, usize)]) {
+    for (name, passed, failed) in groups {
+        let color = if *failed == 0 { Green } else { Red };
+        print!("{}: ", name);
+        term::color(color);
+        print!("{} of {} tests passed", passed, passed + failed);
+        term::reset();
+        println!();
+    }
+    println!();
+}
+
+pub(crate) fn skipped() {
+    term::color(Yellow);
+    println!("skipped");
+    term::reset();
+}
+
 pub(crate) fn ok() {
+    ok_with_warnings(0);
+}
+
+// With TRYBUILD_VERBOSE, the first `ok` for a test also gives the number of
+// warnings in its build.
+pub(crate) fn ok_with_warnings(warnings: usize) {
     term::color(Green);
-    println!("ok");
+    print!("ok");
     term::reset();
+    match warnings {
+        0 => println!(),
+        1 => println!(" (1 warning)"),
+        count => println!(" ({} warnings)", count),
+    }
 }
 
-pub(crate) fn begin_test(test: &Test, show_expected: bool) {
+// `progress` is the number of this test and the total number of tests.
+pub(crate) fn begin_test(
+    project: &Project,
+    test: &Test,
+    description: Option<&str>,
+    show_expected: bool,
+    progress: (usize, usize),
+) {
+    let (number, total) = progress;
     let display_name = if show_expected {
         test.path
             .file_name()
             .unwrap_or_else(|| test.path.as_os_str())
             .to_string_lossy()
     } else {
-        test.path.as_os_str().to_string_lossy()
+        Cow::Owned(display_path(project, &test.path))
     };
 
-    print!("test ");
+    let digits = total.to_string().len();
+    print!("[{:>width$}/{}] test ", number, total, width = digits);
     term::bold();
     print!("{}", display_name);
     term::reset();
 
+    if let Some(description) = description {
+        print!(" ({})", description);
+    }
+
     if show_expected {
         match test.kind {
             TestKind::Pass => print!(" [should pass]"),
@@ -78,10 +312,17 @@ pub(crate) fn begin_test(test: &Test, show_expected: bool) {
     print!(" ... ");
 }
 
-pub(crate) fn failed_to_build(stderr: &str) {
+pub(crate) fn passed_after_retry(attempts: usize) {
+    term::color(Yellow);
+    println!("passed after retry ({} attempts)", attempts);
+    term::reset();
+    println!();
+}
+
+pub(crate) fn failed_to_build(project: &Project, stderr: &str) {
     term::bold_color(Red);
     println!("error");
-    snippet(Red, stderr);
+    snippet(project, Red, stderr);
     println!();
 }
 
@@ -101,9 +342,9 @@ pub(crate) fn output_prefix(kind: &str) {
     print!(" ... ");
 }
 
-pub(crate) fn write_wip(wip_path: &Path, path: &Path, content: &str) {
-    let wip_path = wip_path.to_string_lossy();
-    let path = path.to_string_lossy();
+pub(crate) fn write_wip(project: &Project, wip_path: &Path, path: &Path, content: &str) {
+    let wip_path = display_path(project, wip_path);
+    let path = display_path(project, path);
 
     term::bold_color(Yellow);
     println!("wip");
@@ -115,12 +356,12 @@ pub(crate) fn write_wip(wip_path: &Path, path: &Path, content: &str) {
         "Move this file to `{}` to accept it as correct.",
         path,
     );
-    snippet(Yellow, content);
+    snippet(project, Yellow, content);
     println!();
 }
 
-pub(crate) fn overwrite(path: &Path, content: &str) {
-    let path = path.to_string_lossy();
+pub(crate) fn overwrite(project: &Project, path: &Path, content: &str) {
+    let path = display_path(project, path);
 
     term::bold_color(Yellow);
     println!("wip");
@@ -128,37 +369,145 @@ pub(crate) fn overwrite(path: &Path, content: &str) {
     print!("NOTE");
     term::reset();
     println!(": writing the following output to `{}`.", path);
-    snippet(Yellow, content);
+    snippet(project, Yellow, content);
+    println!();
+}
+
+// Overwriting an existing file shows what changed rather than the whole new
+// content, so that a large TRYBUILD=overwrite run can be reviewed in the log.
+pub(crate) fn overwrite_diff(project: &Project, path: &Path, previous: &str, content: &str) {
+    let path = display_path(project, path);
+
+    term::bold_color(Yellow);
+    println!("wip");
+    println!();
+    print!("NOTE");
+    term::reset();
+    println!(": updating `{}` with the following changes.", path);
+    term::bold_color(Magenta);
+    print!("DIFF:");
+    diff(project, previous, content, project.options.strict_whitespace);
+    println!();
+}
+
+pub(crate) fn mismatch(project: &Project, expected: &str, actual: &str) {
+    term::bold_color(Red);
+    println!("mismatch");
+    term::reset();
+    println!();
+
+    compare(project, expected, actual);
+}
+
+pub(crate) fn not_reproducible(project: &Project, first: &str, second: &str) {
+    term::bold_color(Red);
+    println!("not reproducible");
+    term::color(Red);
+    println!("The compiler output differed when building the test a second time.");
+    term::reset();
+    println!();
+    compare(project, first, second);
+}
+
+// The output as it was before any normalization, in case a normalization is
+// what hides the difference.
+pub(crate) fn raw_output(project: &Project, raw: &str) {
+    term::bold_color(Yellow);
+    println!("RAW OUTPUT:");
+    snippet(project, Yellow, raw);
+    println!();
+}
+
+// The diff against every normalization of the output, the last of which is the
+// one shown by `mismatch`, to tell whether a mismatch depends on normalization.
+pub(crate) fn variations(project: &Project, expected: &str, variations: &normalize::Variations) {
+    let show_whitespace = project.options.strict_whitespace;
+    let count = variations.iter().count();
+    for (i, variation) in variations.iter().enumerate() {
+        term::bold_color(Magenta);
+        print!("DIFF AGAINST VARIATION {}/{}:", i + 1, count);
+        diff(project, expected, variation, show_whitespace);
+        println!();
+    }
+}
+
+pub(crate) fn failure_changed(expected: &[&str], actual: &[&str]) {
+    term::bold_color(Yellow);
+    print!("NOTE");
+    term::reset();
+    println!(
+        ": the test now fails for a different reason; expected {} but found {}.",
+        expected.join(", "),
+        actual.join(", "),
+    );
     println!();
 }
 
-pub(crate) fn mismatch(expected: &str, actual: &str) {
+pub(crate) fn mismatch_bytes(project: &Project, expected: &[u8], actual: &[u8]) {
     term::bold_color(Red);
     println!("mismatch");
     term::reset();
     println!();
+
+    term::bold_color(Yellow);
+    print!("NOTE");
+    term::reset();
+    println!(": the output is not valid UTF-8, so it was compared byte for byte.");
+    println!();
+
+    compare(project, &hex_dump(expected), &hex_dump(actual));
+}
+
+// Renders bytes 16 to a line, in the style of `hexdump -C`:
+//
+//     00000000  68 69 ff 0a                                       |hi..|
+pub(crate) fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (i, chunk) in bytes.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+        let text: String = chunk
+            .iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        dump += &format!("{:08x}  {:<47}  |{}|\n", i * 16, hex.join(" "), text);
+    }
+    dump
+}
+
+fn compare(project: &Project, expected: &str, actual: &str) {
+    let show_whitespace = project.options.strict_whitespace;
+
+    if project.diff == DiffStyle::Columns {
+        // Too narrow to fit two useful columns; use the stacked layout.
+        if let Some(width) = term::width().filter(|&width| width >= 40) {
+            diff_columns(project, expected, actual, width, show_whitespace);
+            println!();
+            return;
+        }
+    }
+
     term::bold_color(Blue);
     println!("EXPECTED:");
-    snippet(Blue, expected);
+    snippet(project, Blue, expected);
     println!();
     term::bold_color(Red);
     println!("ACTUAL OUTPUT:");
-    snippet(Red, actual);
+    snippet(project, Red, actual);
     println!();
     term::bold_color(Magenta);
     print!("DIFF:");
-    diff(expected, actual);
+    diff(project, expected, actual, show_whitespace);
     println!();
 }
 
-pub(crate) fn output(warnings: &str, output: &Output) {
+pub(crate) fn output(project: &Project, warnings: &str, warning_count: usize, output: &Output) {
     let success = output.status.success();
     let stdout = normalize::trim(&output.stdout);
     let stderr = normalize::trim(&output.stderr);
     let has_output = !stdout.is_empty() || !stderr.is_empty();
 
     if success {
-        ok();
+        ok_with_warnings(warning_count);
         if has_output || !warnings.is_empty() {
             println!();
         }
@@ -166,7 +515,9 @@ pub(crate) fn output(warnings: &str, output: &Output) {
         term::bold_color(Red);
         println!("error");
         term::color(Red);
-        if has_output {
+        if let Some(signal) = killed_by(&output.status) {
+            println!("Test case was killed by {}.", signal);
+        } else if has_output {
             println!("Test case failed at runtime.");
         } else {
             println!("Execution of the test case was unsuccessful but there was no output.");
@@ -175,21 +526,94 @@ pub(crate) fn output(warnings: &str, output: &Output) {
         println!();
     }
 
-    self::warnings(warnings);
+    self::warnings(project, warnings);
 
     let color = if success { Yellow } else { Red };
+    streams(project, color, &stdout, &stderr);
+}
+
+pub(crate) fn not_silent(project: &Project, warnings: &str, output: &Output) {
+    term::bold_color(Red);
+    println!("error");
+    term::color(Red);
+    println!("Test case was expected to produce no output.");
+    term::reset();
+    println!();
+
+    self::warnings(project, warnings);
 
+    let stdout = normalize::trim(&output.stdout);
+    let stderr = normalize::trim(&output.stderr);
+    streams(project, Red, &stdout, &stderr);
+}
+
+pub(crate) fn lacks_required(project: &Project, text: &str, stderr: &str) {
+    term::bold_color(Red);
+    println!("error");
+    term::color(Red);
+    println!("Test case output was supposed to contain `{}`.", text);
+    term::reset();
+    println!();
+    streams(project, Red, "", stderr);
+}
+
+pub(crate) fn contains_forbidden(project: &Project, text: &str, stderr: &str) {
+    term::bold_color(Red);
+    println!("error");
+    term::color(Red);
+    println!("Test case output was not supposed to contain `{}`.", text);
+    term::reset();
+    println!();
+    streams(project, Red, "", stderr);
+}
+
+fn streams(project: &Project, color: Color, stdout: &str, stderr: &str) {
     for (name, content) in &[("STDOUT", stdout), ("STDERR", stderr)] {
         if !content.is_empty() {
             term::bold_color(color);
             println!("{}:", name);
-            snippet(color, &normalize::trim(content));
+            snippet(project, color, &normalize::trim(content));
             println!();
         }
     }
 }
 
-pub(crate) fn fail_output(level: Level, stdout: &[u8]) {
+// A crash such as a segfault says something quite different about the test
+// than an ordinary nonzero exit code. Only Unix reports the signal; elsewhere
+// this is always None.
+pub(crate) fn killed_by(status: &ExitStatus) -> Option<String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        // Only the signals whose numbers are the same on every Unix.
+        let signal = status.signal()?;
+        let name = match signal {
+            1 => "SIGHUP",
+            2 => "SIGINT",
+            3 => "SIGQUIT",
+            4 => "SIGILL",
+            5 => "SIGTRAP",
+            6 => "SIGABRT",
+            8 => "SIGFPE",
+            9 => "SIGKILL",
+            11 => "SIGSEGV",
+            13 => "SIGPIPE",
+            14 => "SIGALRM",
+            15 => "SIGTERM",
+            _ => return Some(format!("signal {}", signal)),
+        };
+        Some(format!("signal {} ({})", signal, name))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = status;
+        None
+    }
+}
+
+pub(crate) fn fail_output(project: &Project, level: Level, stdout: &[u8]) {
     let color = match level {
         Fail => Red,
         Warn => Yellow,
@@ -198,43 +622,113 @@ pub(crate) fn fail_output(level: Level, stdout: &[u8]) {
     if !stdout.is_empty() {
         term::bold_color(color);
         println!("STDOUT:");
-        snippet(color, &normalize::trim(stdout));
+        snippet(project, color, &normalize::trim(stdout));
         println!();
     }
 }
 
-pub(crate) fn warnings(warnings: &str) {
+pub(crate) fn warnings(project: &Project, warnings: &str) {
+    warnings_section(project, "WARNINGS", warnings);
+}
+
+// Warnings cargo gave about the generated project itself, like unused manifest
+// keys, rather than about the code of any test.
+pub(crate) fn cargo_warnings(project: &Project, warnings: &str) {
+    warnings_section(project, "CARGO WARNINGS", warnings);
+}
+
+fn warnings_section(project: &Project, title: &str, warnings: &str) {
     if warnings.is_empty() {
         return;
     }
 
     term::bold_color(Yellow);
-    println!("WARNINGS:");
-    snippet(Yellow, warnings);
+    println!("{}:", title);
+    snippet(project, Yellow, warnings);
     println!();
 }
 
-pub(crate) fn dotted_line() {
-    println!("{}", "┈".repeat(60));
+// Returns how many of `count` lines are left out by `max_output_lines`. The
+// comparison and the files written always use the full output.
+fn omitted(project: &Project, count: usize) -> usize {
+    match project.options.max_output_lines {
+        None | Some(0) => 0,
+        Some(max) => count.saturating_sub(max),
+    }
+}
+
+fn more_lines(count: usize) {
+    let ellipsis = ellipsis();
+    println!("({}{} more lines{})", ellipsis, count, ellipsis);
+}
+
+// Used when there is no content to measure the separator against.
+const DEFAULT_WIDTH: usize = 60;
+
+fn dotted_line(width: usize) {
+    let dot = if term::ascii() { "-" } else { "┈" };
+    println!("{}", dot.repeat(width));
 }
 
-fn snippet(color: Color, content: &str) {
+fn ellipsis() -> &'static str {
+    if term::ascii() {
+        "..."
+    } else {
+        "…"
+    }
+}
+
+// The separator spans the longest line being framed, but no wider than the
+// terminal.
+fn frame_width<'a, I: IntoIterator<Item = &'a str>>(lines: I) -> usize {
+    let longest = lines
+        .into_iter()
+        .map(|line| line.chars().count())
+        .max()
+        .filter(|&longest| longest > 0)
+        .unwrap_or(DEFAULT_WIDTH);
+
+    match term::width() {
+        Some(width) => longest.min(width),
+        None => longest,
+    }
+}
+
+fn snippet(project: &Project, color: Color, content: &str) {
+    let frame = frame_width(content.lines());
     term::color(color);
-    dotted_line();
+    dotted_line(frame);
+
+    let width = term::width();
+    let truncate = env::enabled("TRYBUILD_TRUNCATE");
+
+    let count = content.lines().count();
+    let omitted = omitted(project, count);
 
     // Color one line at a time because Travis does not preserve color setting
     // across output lines.
-    for line in content.lines() {
+    for line in content.lines().take(count - omitted) {
+        let pieces = match width {
+            Some(width) => wrap(line, width, truncate),
+            None => vec![line.to_owned()],
+        };
+        for piece in pieces {
+            term::color(color);
+            println!("{}", piece);
+        }
+    }
+
+    if omitted > 0 {
         term::color(color);
-        println!("{}", line);
+        more_lines(omitted);
     }
 
     term::color(color);
-    dotted_line();
+    dotted_line(frame);
     term::reset();
 }
 
-fn diff(expected: &str, actual: &str) {
+fn diff(project: &Project, expected: &str, actual: &str, show_whitespace: bool) {
     use diff::Result as Diff;
 
     term::color(Red);
@@ -242,27 +736,214 @@ fn diff(expected: &str, actual: &str) {
     term::color(Green);
     println!("+actual ");
 
+    // One extra column for the leading `+`, `-` or space.
+    let frame = frame_width(expected.lines().chain(actual.lines())) + 1;
     term::bold_color(Magenta);
-    dotted_line();
+    dotted_line(frame);
 
-    for diff in diff::lines(expected, actual) {
+    let diffs = diff::lines(expected, actual);
+    let omitted = omitted(project, diffs.len());
+    let shown = diffs.len() - omitted;
+
+    for diff in diffs.into_iter().take(shown) {
         match diff {
             Diff::Both(x, _) => {
                 term::reset();
-                println!(" {}", x);
+                println!(" {}", visible(x, show_whitespace));
             }
             Diff::Right(x) => {
                 term::color(Green);
-                println!("+{}", x);
+                println!("+{}", visible(x, show_whitespace));
             }
             Diff::Left(x) => {
                 term::color(Red);
-                println!("-{}", x);
+                println!("-{}", visible(x, show_whitespace));
             }
         }
     }
 
+    if omitted > 0 {
+        term::reset();
+        more_lines(omitted);
+    }
+
+    term::bold_color(Magenta);
+    dotted_line(frame);
+    term::reset();
+}
+
+fn diff_columns(
+    project: &Project,
+    expected: &str,
+    actual: &str,
+    width: usize,
+    show_whitespace: bool,
+) {
+    use diff::Result as Diff;
+
+    let separator = if term::ascii() { " | " } else { " │ " };
+    let column = width.saturating_sub(separator.chars().count()) / 2;
+
+    let mut rows = Vec::new();
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for diff in diff::lines(expected, actual) {
+        match diff {
+            Diff::Left(x) => removed.push(x),
+            Diff::Right(x) => added.push(x),
+            Diff::Both(x, y) => {
+                pair_changes(&mut rows, &mut removed, &mut added);
+                rows.push((Some(x), Some(y), false));
+            }
+        }
+    }
+    pair_changes(&mut rows, &mut removed, &mut added);
+
+    term::bold_color(Blue);
+    print!("{}", fit("EXPECTED:", column));
+    term::bold_color(Magenta);
+    print!("{}", separator);
+    term::bold_color(Red);
+    println!("ACTUAL OUTPUT:");
+
+    term::bold_color(Magenta);
+    dotted_line(width);
+
+    let omitted = omitted(project, rows.len());
+    let shown = rows.len() - omitted;
+
+    for (left, right, changed) in rows.into_iter().take(shown) {
+        if changed {
+            term::color(Red);
+        } else {
+            term::reset();
+        }
+        print!("{}", fit(&visible(left.unwrap_or(""), show_whitespace), column));
+        term::color(Magenta);
+        print!("{}", separator);
+        if changed {
+            term::color(Green);
+        } else {
+            term::reset();
+        }
+        println!("{}", visible(right.unwrap_or(""), show_whitespace));
+    }
+
+    if omitted > 0 {
+        term::reset();
+        more_lines(omitted);
+    }
+
     term::bold_color(Magenta);
-    dotted_line();
+    dotted_line(width);
     term::reset();
 }
+
+// Makes trailing whitespace stand out, for when it is significant.
+fn visible(line: &str, show_whitespace: bool) -> Cow<'_, str> {
+    let content = line.trim_end();
+    if !show_whitespace || content.len() == line.len() {
+        return Cow::Borrowed(line);
+    }
+
+    let dot = if term::ascii() { "~" } else { "·" };
+    let trailing = line[content.len()..].chars().count();
+    Cow::Owned(content.to_owned() + &dot.repeat(trailing))
+}
+
+// Lines removed from the expected output and lines added in the actual output
+// are shown next to each other, padding whichever side has fewer lines.
+fn pair_changes<'a>(
+    rows: &mut Vec<(Option<&'a str>, Option<&'a str>, bool)>,
+    removed: &mut Vec<&'a str>,
+    added: &mut Vec<&'a str>,
+) {
+    let len = removed.len().max(added.len());
+    let mut removed = removed.drain(..);
+    let mut added = added.drain(..);
+    for _ in 0..len {
+        rows.push((removed.next(), added.next(), true));
+    }
+}
+
+// Splits a line that is too wide for the terminal into several, indenting the
+// continuations, or cuts it short with an ellipsis if `truncate` is set.
+fn wrap(line: &str, width: usize, truncate: bool) -> Vec<String> {
+    const INDENT: &str = "    ";
+
+    let chars: Vec<char> = line.chars().collect();
+    if chars.len() <= width || width <= INDENT.len() {
+        return vec![line.to_owned()];
+    }
+
+    if truncate {
+        let ellipsis = ellipsis();
+        let keep = width.saturating_sub(ellipsis.chars().count());
+        let truncated: String = chars[..keep].iter().collect();
+        return vec![truncated + ellipsis];
+    }
+
+    let mut pieces = vec![chars[..width].iter().collect::<String>()];
+    for rest in chars[width..].chunks(width - INDENT.len()) {
+        pieces.push(INDENT.to_owned() + &rest.iter().collect::<String>());
+    }
+    pieces
+}
+
+// Truncates or pads `line` so that it occupies exactly `width` columns.
+fn fit(line: &str, width: usize) -> String {
+    let len = line.chars().count();
+    if len > width {
+        let ellipsis = ellipsis();
+        let keep = width.saturating_sub(ellipsis.chars().count());
+        line.chars().take(keep).collect::<String>() + ellipsis
+    } else {
+        format!("{}{}", line, " ".repeat(width - len))
+    }
+}
+
+// Paths are shown relative to the workspace root, which is shorter than an
+// absolute path and can still be pasted into a shell there. Relative paths are
+// relative to the crate under test to begin with. Anything outside of the
+// workspace is shown as is.
+fn display_path(project: &Project, path: &Path) -> String {
+    relative_to(&project.workspace, &project.source_dir, path)
+}
+
+fn relative_to(workspace: &Path, source_dir: &Path, path: &Path) -> String {
+    match source_dir.join(path).strip_prefix(workspace) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.to_string_lossy().into_owned(),
+        _ => path.to_string_lossy().into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ellipsis, hex_dump, relative_to, wrap};
+    use std::path::Path;
+
+    #[test]
+    fn wrap_long_lines() {
+        assert_eq!(wrap("short", 10, false), ["short"]);
+        assert_eq!(wrap("0123456789abcdefghij", 10, false), ["0123456789", "    abcdef", "    ghij"]);
+        let truncated = wrap("0123456789abcdefghij", 10, true);
+        assert_eq!(truncated[0].chars().count(), 10);
+        assert!(truncated[0].ends_with(ellipsis()));
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(hex_dump(b"hi\xff\n"), format!("00000000  68 69 ff 0a{}  |hi..|\n", " ".repeat(36)));
+        assert_eq!(hex_dump(&[b'a'; 17]).lines().nth(1), Some(format!("00000010  61{}  |a|", " ".repeat(45)).as_str()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn workspace_relative() {
+        let workspace = Path::new("/w");
+        let source_dir = Path::new("/w/crates/macros");
+        assert_eq!(relative_to(workspace, source_dir, Path::new("tests/ui/a.rs")), "crates/macros/tests/ui/a.rs");
+        assert_eq!(relative_to(workspace, source_dir, Path::new("/w/shared/a.rs")), "shared/a.rs");
+        assert_eq!(relative_to(workspace, source_dir, Path::new("/elsewhere/a.rs")), "/elsewhere/a.rs");
+    }
+}
@@ -1,5 +1,8 @@
 use crate::Test;
-use crate::cargo::Project;
+use crate::cargo::{self, Project};
+use crate::env;
+use crate::options::Options;
+use std::path::Path;
 
 pub fn trim<S: AsRef<[u8]>>(output: S) -> String {
     let bytes = output.as_ref();
@@ -15,48 +18,435 @@ pub fn trim<S: AsRef<[u8]>>(output: S) -> String {
     normalized
 }
 
+// The parts of normalization that are the same for every test of a suite,
+// worked out once when the project is prepared.
+#[derive(Clone, Debug)]
+pub struct Context {
+    source_dir: String,
+    normalizations: Vec<Normalization>,
+    collapse_std: bool,
+    collapse_backtrace: bool,
+}
+
+impl Context {
+    pub fn new(source_dir: &Path, options: &Options) -> Self {
+        let mut normalizations = vec![Basic, StripCouldNotCompile];
+        if options.proc_macro_paths {
+            normalizations.push(ProcMacroPaths);
+        }
+
+        Context {
+            source_dir: source_dir.to_string_lossy().into_owned(),
+            normalizations,
+            collapse_std: !options.build_std.is_empty(),
+            collapse_backtrace: options.backtrace || env::enabled("RUST_BACKTRACE"),
+        }
+    }
+}
+
+#[allow(clippy::needless_borrow)]
 pub fn diagnostics(output: &[u8], test: &Test, project: &Project) -> Variations {
     let mut from_bytes = String::from_utf8_lossy(&output).to_string();
-    from_bytes = from_bytes.replace("\r\n", "\n")
-            .replace(&test.name, "$CRATE");
+    from_bytes = from_bytes.replace("\r\n", "\n");
+
+    if test.inline.is_some() {
+        let inline_path = cargo::inline_path(project, test);
+        from_bytes = replace_generated(&from_bytes, project, &inline_path, "$INLINE");
+    }
+    if project.options.prelude.is_some() {
+        let prelude_path = cargo::prelude_path(project, test);
+        from_bytes = replace_generated(&from_bytes, project, &prelude_path, "$PRELUDE");
+    }
+
+    from_bytes = from_bytes.replace(&test.name, "$CRATE");
+
+    // Refer to a symlinked test by the link, not by the file it points to.
+    let path = project.source_dir.join(&test.path);
+    let bin_path = cargo::bin_path(project, test);
+    if bin_path != path {
+        from_bytes = from_bytes.replace(&*bin_path.to_string_lossy(), &path.to_string_lossy());
+    }
 
-    let source_dir = project.source_dir.to_string_lossy();
-    let variations = [Basic, StripCouldNotCompile]
+    let context = &project.normalize;
+    if context.collapse_std {
+        from_bytes = collapse_std_source(&from_bytes);
+    }
+    if context.collapse_backtrace {
+        from_bytes = collapse_backtrace(&from_bytes);
+    }
+
+    let strict = project.options.strict_whitespace;
+    let mut variations: Vec<String> = context
+        .normalizations
         .iter()
-        .map(|normalization| apply(&from_bytes, *normalization, &source_dir))
+        .map(|normalization| apply(&from_bytes, *normalization, &context.source_dir, strict))
         .collect();
 
-    Variations { variations }
+    // Each custom level builds on the most normalized variation so far, so
+    // that the last one remains the preferred.
+    for normalization in &project.options.normalizations {
+        let normalized = (normalization.0)(variations.last().unwrap());
+        variations.push(normalized);
+    }
+
+    let mut variations = Variations {
+        variations,
+        prefer_strict: project.options.prefer_strict,
+    };
+
+    // The hook's result is final: it is the only form compared and written.
+    if let Some(normalizer) = &project.options.normalizer {
+        let normalized = (normalizer.0)(variations.preferred());
+        variations.variations = vec![normalized];
+    }
+
+    variations
+}
+
+// With -Z build-std the standard library is compiled from the rust-src
+// component, so diagnostics pointing into it mention the machine-specific
+// sysroot rather than the usual remapped `/rustc/$HASH/library/`:
+//
+//     --> /home/user/.rustup/toolchains/nightly-x86_64-unknown-linux-gnu/lib/rustlib/src/rust/library/core/src/fmt/mod.rs:100:1
+//
+// becomes
+//
+//     --> $RUST/core/src/fmt/mod.rs:100:1
+fn collapse_std_source(output: &str) -> String {
+    const LIBRARY: &str = "/lib/rustlib/src/rust/library/";
+
+    let mut collapsed = String::new();
+    for line in output.lines() {
+        let unix = line.replace('\\', "/");
+        match unix.find(LIBRARY) {
+            Some(end) => {
+                let start = unix[..end]
+                    .rfind(|ch: char| ch.is_whitespace() || ch == '(')
+                    .map_or(0, |i| i + 1);
+                collapsed += &unix[..start];
+                collapsed += "$RUST/";
+                collapsed += &unix[end + LIBRARY.len()..];
+            }
+            None => collapsed += line,
+        }
+        collapsed.push('\n');
+    }
+    collapsed
+}
+
+// Panic backtraces embed the toolchain commit hash, frame addresses and
+// symbol hashes, none of which are stable between machines:
+//
+//     thread 'main' (4021) panicked at $DIR/tests/ui/run-fail.rs:2:5:
+//     stack backtrace:
+//        3:     0x55d5c0a1b2c3 - trybuild001::main::h0123456789abcdef
+//                  at /rustc/59807616e1fa2540724bfbac14d7976d7e4a3860/library/core/src/panicking.rs:75:14
+//
+// becomes
+//
+//     thread 'main' panicked at $DIR/tests/ui/run-fail.rs:2:5:
+//     stack backtrace:
+//        3: trybuild001::main
+//                  at $RUST/core/src/panicking.rs:75:14
+//
+// Only the indented lines following `stack backtrace:` are rewritten, so that
+// whatever the test itself prints is left alone.
+fn collapse_backtrace(output: &str) -> String {
+    let mut collapsed = String::new();
+    let mut in_backtrace = false;
+    for line in output.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        in_backtrace &= !indent.is_empty();
+        if line == "stack backtrace:" {
+            in_backtrace = true;
+            collapsed += line;
+        } else if let Some(line) = thread_panicked(line) {
+            collapsed += &line;
+        } else if !in_backtrace {
+            collapsed += line;
+        } else if let Some(frame) = frame_symbol(trimmed) {
+            collapsed += indent;
+            collapsed += &frame;
+        } else if let Some(location) = trimmed.strip_prefix("at /rustc/") {
+            match location.find("/library/") {
+                Some(end) if location[..end].bytes().all(|b| b.is_ascii_hexdigit()) => {
+                    collapsed += indent;
+                    collapsed += "at $RUST/";
+                    collapsed += &location[end + "/library/".len()..];
+                }
+                _ => collapsed += line,
+            }
+        } else {
+            collapsed += line;
+        }
+        collapsed.push('\n');
+    }
+    collapsed
+}
+
+// `thread 'main' (4021) panicked at ...` => `thread 'main' panicked at ...`
+fn thread_panicked(line: &str) -> Option<String> {
+    let rest = line.strip_prefix("thread '")?;
+    let name_end = rest.find("' (")? + "thread '".len() + 1;
+    let after = &line[name_end..];
+    let id_end = after.find(") panicked at ")?;
+    if !after[2..id_end].bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(line[..name_end].to_owned() + &after[id_end + 1..])
+}
+
+// `3:     0x55d5c0a1b2c3 - main::h0123456789abcdef` => `3: main`
+fn frame_symbol(line: &str) -> Option<String> {
+    let colon = line.find(':')?;
+    let number = &line[..colon];
+    if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let address = line[colon + 1..].trim_start().strip_prefix("0x")?;
+    let digits = address.bytes().take_while(u8::is_ascii_hexdigit).count();
+    if digits == 0 {
+        return None;
+    }
+    let symbol = address[digits..].strip_prefix(" - ")?;
+
+    let symbol = match symbol.rfind("::h") {
+        Some(i)
+            if symbol.len() - i == 19
+                && symbol[i + 3..].bytes().all(|b| b.is_ascii_hexdigit()) =>
+        {
+            &symbol[..i]
+        }
+        _ => symbol,
+    };
+    Some(format!("{}: {}", number, symbol))
+}
+
+// Picks cargo's own warnings out of its stderr, for example about unused
+// manifest keys:
+//
+//     warning: unused manifest key: package.foo
+//
+// Progress lines like `   Compiling foo v0.1.0` are skipped, as are compiler
+// diagnostics, which point at source code with a `-->` line, and cargo's
+// `generated N warnings` summaries of them.
+pub fn cargo_warnings(stderr: &str) -> String {
+    let mut warnings = String::new();
+    let mut block: Option<String> = None;
+
+    let mut finish = |block: Option<String>| {
+        if let Some(block) = block {
+            let is_rustc = block.lines().any(|line| line.trim_start().starts_with("--> "));
+            let is_summary = block.lines().next().map_or(false, |line| {
+                line.contains(" generated ") && line.contains(" warning")
+            });
+            if !is_rustc && !is_summary {
+                warnings += &block;
+            }
+        }
+    };
+
+    for line in stderr.lines() {
+        if line.starts_with("warning: ") {
+            finish(block.take());
+            block = Some(format!("{}\n", line));
+        } else if let Some(current) = &mut block {
+            let is_continuation = line.starts_with(' ') && !is_progress(line);
+            if is_continuation {
+                *current += line;
+                current.push('\n');
+            } else {
+                finish(block.take());
+            }
+        }
+    }
+    finish(block);
+
+    warnings
+}
+
+// `   Compiling foo v0.1.0`, `    Finished ...`
+fn is_progress(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    let word = trimmed.split(' ').next().unwrap_or("");
+    let mut chars = word.chars();
+    chars.next().map_or(false, |ch| ch.is_ascii_uppercase())
+        && chars.all(|ch| ch.is_ascii_lowercase())
+        && word.len() < trimmed.len()
+}
+
+// The kind of each error in compiler output: its code, like `E0277`, or for
+// errors without a code, the message itself.
+pub fn error_kinds(output: &str) -> Vec<&str> {
+    let mut kinds = Vec::new();
+    for line in output.lines() {
+        let kind = if let Some(rest) = line.strip_prefix("error[") {
+            match rest.find(']') {
+                Some(end) => &rest[..end],
+                None => continue,
+            }
+        } else if let Some(message) = line.strip_prefix("error: ") {
+            if is_summary(message) {
+                continue;
+            }
+            message
+        } else {
+            continue;
+        };
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    kinds
+}
+
+// Splits compiler output into its warnings and everything else. Each
+// diagnostic starts with an unindented `warning` or `error` line and extends
+// up to the next one.
+pub fn split_warnings(output: &[u8]) -> (String, String) {
+    let output = String::from_utf8_lossy(output);
+    let mut warnings = String::new();
+    let mut rest = String::new();
+    let mut in_warning = false;
+    for line in output.split_inclusive('\n') {
+        if line.starts_with("warning:") || line.starts_with("warning[") {
+            in_warning = true;
+        } else if line.starts_with("error:") || line.starts_with("error[") {
+            in_warning = false;
+        }
+        if in_warning {
+            warnings.push_str(line);
+        } else {
+            rest.push_str(line);
+        }
+    }
+    (warnings, rest)
+}
+
+// The number of errors in compiler output, not counting the summary at the
+// end.
+pub fn error_count(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| match line.strip_prefix("error: ") {
+            Some(message) => !is_summary(message),
+            None => line.starts_with("error["),
+        })
+        .count()
+}
+
+// The number of warnings in compiler output, not counting the summaries that
+// rustc and cargo add.
+pub fn warning_count(output: &str) -> usize {
+    output
+        .lines()
+        .filter(|line| match line.strip_prefix("warning: ") {
+            Some(message) => !is_warning_summary(message),
+            None => line.starts_with("warning["),
+        })
+        .count()
+}
+
+fn is_warning_summary(message: &str) -> bool {
+    message.ends_with(" warning emitted")
+        || message.ends_with(" warnings emitted")
+        || message.contains(") generated ")
+}
+
+// Cargo refers to files that trybuild generates within the project by their
+// relative path, and `include!` by the absolute one.
+fn replace_generated(text: &str, project: &Project, path: &Path, token: &str) -> String {
+    let relative = path.strip_prefix(&project.dir).unwrap_or(path);
+    text.replace(&*path.to_string_lossy(), token)
+        .replace(&*relative.to_string_lossy(), token)
+}
+
+fn is_summary(message: &str) -> bool {
+    message.starts_with("aborting due to ") || message.starts_with("could not compile ")
 }
 
+/// The output of a test, normalized in increasingly lenient ways.
 pub struct Variations {
     variations: Vec<String>,
+    prefer_strict: bool,
 }
 
 impl Variations {
+    /// The form that gets written as the expected output: the most normalized
+    /// one, or the least normalized one with `TestCases::prefer_strict`.
     pub fn preferred(&self) -> &str {
-        self.variations.last().unwrap()
+        if self.prefer_strict {
+            self.variations.first().unwrap()
+        } else {
+            self.variations.last().unwrap()
+        }
+    }
+
+    /// All the forms, from the least to the most normalized.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.variations.iter().map(String::as_str)
     }
 
+    /// Whether `f` holds for any of the forms.
     pub fn any<F: FnMut(&str) -> bool>(&self, mut f: F) -> bool {
         self.variations.iter().any(|stderr| f(stderr))
     }
+
+    pub(crate) fn sort_lines(&mut self) {
+        for variation in &mut self.variations {
+            *variation = sort_lines(variation);
+        }
+    }
+}
+
+// For output whose line order legitimately varies between runs.
+pub fn sort_lines(text: &str) -> String {
+    let mut lines: Vec<&str> = text.lines().collect();
+    lines.sort_unstable();
+
+    let mut sorted = lines.join("\n");
+    if text.ends_with('\n') {
+        sorted.push('\n');
+    }
+    sorted
 }
 
-#[derive(PartialOrd, PartialEq, Copy, Clone)]
+#[derive(PartialOrd, PartialEq, Copy, Clone, Debug)]
 enum Normalization {
     Basic,
     StripCouldNotCompile,
+    ProcMacroPaths,
 }
 
 use self::Normalization::*;
 
-fn apply(original: &str, normalization: Normalization, source_dir: &str) -> String {
+// With `strict_whitespace`, trailing whitespace on each line is kept so that
+// it takes part in the comparison; only trailing newlines are dropped.
+#[allow(clippy::borrow_deref_ref)]
+fn apply(
+    original: &str,
+    normalization: Normalization,
+    source_dir: &str,
+    strict_whitespace: bool,
+) -> String {
     let mut normalized = String::new();
+    let mut in_cargo_config_warning = false;
 
     for line in original.lines() {
+        if is_cargo_config_warning(line) {
+            in_cargo_config_warning = true;
+            continue;
+        }
+        if in_cargo_config_warning && is_warning_continuation(line) {
+            continue;
+        }
+        in_cargo_config_warning = false;
+
         if let Some(line) = filter(line, normalization) {
-            let line = line.trim_end().replace(&*source_dir, "$DIR");
+            let line = if strict_whitespace { &line } else { line.trim_end() };
+            let line = line.replace(&*source_dir, "$DIR");
 
             if cfg!(windows) {
                 normalized += &line
@@ -72,10 +462,67 @@ fn apply(original: &str, normalization: Normalization, source_dir: &str) -> Stri
         }
     }
 
+    if strict_whitespace {
+        let len = normalized.trim_end_matches('\n').len();
+        normalized.truncate(len);
+        if !normalized.is_empty() {
+            normalized.push('\n');
+        }
+        return normalized;
+    }
+
     trim(normalized)
 }
 
+// Spans within code generated by a procedural macro have no file of their
+// own, and how a derive is underlined differs between compiler versions.
+// Locations like `--> <proc-macro>:1:5` become `--> $PROC_MACRO`, and the
+// underline in front of `in this derive macro expansion` is dropped.
+fn proc_macro_location(line: &str) -> Option<String> {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with("--> <") || trimmed.starts_with("::: <") {
+        let indent = line.len() - trimmed.len();
+        return Some(format!("{}{} $PROC_MACRO", &line[..indent], &trimmed[..3]));
+    }
+
+    let label = "in this derive macro expansion";
+    if line.ends_with(label) {
+        let gutter = line.find('|')?;
+        return Some(format!("{} {}", &line[..=gutter], label));
+    }
+
+    None
+}
+
+// Cargo warns about its own configuration, such as a deprecated
+// `.cargo/config` without the `.toml` extension, in the same stream as the
+// compiler's diagnostics. Only warnings naming a `.cargo/config` file are
+// dropped, which no rustc diagnostic does, along with the help and notes that
+// follow them:
+//
+//     warning: `/path/to/.cargo/config` is deprecated in favor of `config.toml`
+//       |
+//       = help: if you need to support cargo 1.38 or earlier, you can symlink `config` to `config.toml`
+fn is_cargo_config_warning(line: &str) -> bool {
+    line.starts_with("warning: ")
+        && (line.contains("/.cargo/config") || line.contains("\\.cargo\\config"))
+}
+
+fn is_warning_continuation(line: &str) -> bool {
+    line.starts_with("note: ")
+        || line.starts_with("help: ")
+        || line.trim_end() == "  |"
+        || line.starts_with("  = ")
+}
+
+#[allow(clippy::collapsible_if)]
 fn filter(line: &str, normalization: Normalization) -> Option<String> {
+    if normalization >= ProcMacroPaths {
+        if let Some(line) = proc_macro_location(line) {
+            return Some(line);
+        }
+    }
+
     if line.trim_start().starts_with("--> ") {
         if let Some(cut_end) = line.rfind(&['/', '\\'][..]) {
             let cut_start = line.find('>').unwrap() + 2;
@@ -107,7 +554,166 @@ mod tests {
         let s = super::apply(
             "C:\\\\foo\\bar",
             super::Normalization::StripCouldNotCompile,
-            "C:\\\\foo\\bar");
+            "C:\\\\foo\\bar",
+            false);
         assert_eq!(s, "$DIR\n")
     }
+
+    #[test]
+    fn cargo_config_warning() {
+        let s = super::apply(
+            "warning: `/p/target/tests/trybuild/.cargo/config` is deprecated in favor of `config.toml`\n\
+             \x20 |\n\
+             \x20 = help: if you need to support cargo 1.38 or earlier, you can symlink `config` to `config.toml`\n\
+             warning: `/p/.cargo/config` is deprecated in favor of `config.toml`\n\
+             note: If you need to support cargo 1.38 or earlier, you can symlink `config` to `config.toml`\n\
+             warning: unused variable: `x`\n\
+             \x20 |\n\
+             \x20 = note: `#[warn(unused_variables)]` on by default\n",
+            super::Normalization::Basic,
+            "/p",
+            false);
+        assert_eq!(s,
+            "warning: unused variable: `x`\n\
+             \x20 |\n\
+             \x20 = note: `#[warn(unused_variables)]` on by default\n");
+    }
+
+    #[test]
+    fn std_source() {
+        let s = super::collapse_std_source(
+            "  --> /home/u/.rustup/toolchains/nightly/lib/rustlib/src/rust/library/core/src/lib.rs:1:1\n");
+        assert_eq!(s, "  --> $RUST/core/src/lib.rs:1:1\n");
+    }
+
+    #[test]
+    fn cargo_warnings() {
+        let s = super::cargo_warnings(
+            "warning: unused manifest key: package.foo\n\
+             \x20  Compiling dep v0.1.0\n\
+             warning: unused variable: `x`\n\
+             \x20--> src/lib.rs:1:5\n\
+             warning: `dep` (lib) generated 1 warning\n\
+             warning: file `a.rs` found to be present in multiple build targets:\n\
+             \x20 * `bin` target `a`\n\
+             \x20   Finished `dev` profile\n");
+        assert_eq!(s,
+            "warning: unused manifest key: package.foo\n\
+             warning: file `a.rs` found to be present in multiple build targets:\n\
+             \x20 * `bin` target `a`\n");
+    }
+
+    #[test]
+    fn error_kinds() {
+        let kinds = super::error_kinds(
+            "error[E0277]: the trait bound `T: Send` is not satisfied\n\
+             \x20--> $DIR/a.rs:1:1\n\
+             error: cannot find macro `foo` in this scope\n\
+             error[E0277]: the trait bound `U: Send` is not satisfied\n\
+             error: aborting due to 3 previous errors\n");
+        assert_eq!(kinds, ["E0277", "cannot find macro `foo` in this scope"]);
+    }
+
+    #[test]
+    fn error_count() {
+        let count = super::error_count(
+            "error[E0277]: the trait bound `T: Send` is not satisfied\n\
+             \x20--> $DIR/a.rs:1:1\n\
+             \x20 = note: error: not a diagnostic\n\
+             error: cannot find macro `foo` in this scope\n\
+             warning: unused import\n\
+             error: aborting due to 2 previous errors\n\
+             error: could not compile `$CRATE`\n");
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn split_warnings() {
+        let (warnings, errors) = super::split_warnings(
+            b"warning: unused variable: `x`\n\
+              \x20--> $DIR/a.rs:2:9\n\
+              \n\
+              error[E0308]: mismatched types\n\
+              \x20--> $DIR/a.rs:3:17\n\
+              \n\
+              warning: `$CRATE` (bin) generated 1 warning\n\
+              error: could not compile `$CRATE`\n");
+        assert_eq!(warnings,
+            "warning: unused variable: `x`\n\
+             \x20--> $DIR/a.rs:2:9\n\
+             \n\
+             warning: `$CRATE` (bin) generated 1 warning\n");
+        assert_eq!(errors,
+            "error[E0308]: mismatched types\n\
+             \x20--> $DIR/a.rs:3:17\n\
+             \n\
+             error: could not compile `$CRATE`\n");
+    }
+
+    #[test]
+    fn backtrace() {
+        let s = super::collapse_backtrace(
+            "thread 'main' (4021) panicked at src/main.rs:2:5:\n\
+             stack backtrace:\n\
+             \x20  0:     0x55d5c0a1b2c3 - std::rt::lang_start::h0123456789abcdef\n\
+             \x20  1: core::panicking::panic_fmt\n\
+             \x20            at /rustc/59807616e1fa2540/library/core/src/panicking.rs:75:14\n\
+             \x20  2:     0x7f00 - <unknown>\n\
+             \x20  3: 0x10 not a frame\n\
+             note: Some details are omitted\n\
+             \x20  1:     0x10 - main::h0123456789abcdef\n\
+             error: 1: not a frame\n");
+        assert_eq!(s,
+            "thread 'main' panicked at src/main.rs:2:5:\n\
+             stack backtrace:\n\
+             \x20  0: std::rt::lang_start\n\
+             \x20  1: core::panicking::panic_fmt\n\
+             \x20            at $RUST/core/src/panicking.rs:75:14\n\
+             \x20  2: <unknown>\n\
+             \x20  3: 0x10 not a frame\n\
+             note: Some details are omitted\n\
+             \x20  1:     0x10 - main::h0123456789abcdef\n\
+             error: 1: not a frame\n");
+    }
+
+    #[test]
+    fn prefer_strict() {
+        let mut variations = super::Variations {
+            variations: vec!["strict".to_owned(), "lenient".to_owned()],
+            prefer_strict: false,
+        };
+        assert_eq!(variations.preferred(), "lenient");
+        variations.prefer_strict = true;
+        assert_eq!(variations.preferred(), "strict");
+    }
+
+    #[test]
+    fn proc_macro_paths() {
+        let s = super::apply(
+            "error: expected a struct\n\
+             \x20--> <proc-macro>:1:5\n\
+             \x20 |\n\
+             3 | #[derive(Thing)]\n\
+             \x20 |          ----- in this derive macro expansion\n",
+            super::Normalization::ProcMacroPaths,
+            "/p",
+            false);
+        assert_eq!(s,
+            "error: expected a struct\n\
+             \x20--> $PROC_MACRO\n\
+             \x20 |\n\
+             3 | #[derive(Thing)]\n\
+             \x20 | in this derive macro expansion\n");
+    }
+
+    #[test]
+    fn warning_count() {
+        let output = "warning: unused variable: `x`\n\
+                      \x20 |\n\
+                      warning[E0170]: pattern binding is named the same as a variant\n\
+                      error: literal out of range for `u8`\n\
+                      warning: `trybuild-tests` (bin \"trybuild000\") generated 2 warnings\n\
+                      warning: 2 warnings emitted\n";
+        assert_eq!(super::warning_count(output), 2);
+    }
 }
@@ -0,0 +1,132 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap as Map;
+use std::fmt::{self, Debug};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use glob::Pattern;
+
+use crate::cargo::Project;
+use crate::manifest::Manifest;
+
+#[derive(Clone, Default, Debug)]
+pub struct Options {
+    pub normalizer: Option<Normalizer>,
+    pub normalizations: Vec<Normalizer>,
+    pub edit_manifest: Option<ManifestEditor>,
+    pub snapshot_file: Option<PathBuf>,
+    pub strict_whitespace: bool,
+    pub proc_macro_paths: bool,
+    pub prefer_strict: bool,
+    pub build_std: Vec<String>,
+    pub cargo_args: Vec<String>,
+    pub cargo_config: Vec<String>,
+    pub rustc_wrapper: Option<PathBuf>,
+    pub cargo_bin: Option<PathBuf>,
+    pub incremental: Option<bool>,
+    pub jobs: Option<usize>,
+    pub flaky: Map<PathBuf, usize>,
+    pub unordered: Map<PathBuf, ()>,
+    pub silent: Map<PathBuf, ()>,
+    pub conditions: Map<PathBuf, Condition>,
+    pub error_count: Map<PathBuf, usize>,
+    pub descriptions: Map<PathBuf, String>,
+    pub contains: Map<PathBuf, Vec<String>>,
+    pub not_contains: Map<PathBuf, Vec<String>>,
+    pub rustflags: Map<PathBuf, Vec<String>>,
+    pub backtrace: bool,
+    pub all_features: bool,
+    pub keep_default_features: bool,
+    pub strict_features: bool,
+    pub max_output_lines: Option<usize>,
+    pub require_stderr: bool,
+    pub check_reproducible: bool,
+    pub batch_compile_fail: bool,
+    pub keep_previous_wip: bool,
+    pub separate_warnings: bool,
+    pub self_features: Vec<String>,
+    pub workspace_members: Vec<PathBuf>,
+    pub edition: Option<String>,
+    pub prelude: Option<String>,
+    pub crate_name: Option<String>,
+    pub manifest_dir: Option<PathBuf>,
+    pub stderr_ext: Option<String>,
+    pub expected_dir: Option<(PathBuf, PathBuf)>,
+    pub snapshot_header: bool,
+    pub base_dir: Option<PathBuf>,
+    pub allow_outside_paths: bool,
+    pub expected_count: Option<usize>,
+    pub check_orphans: bool,
+    #[cfg(feature = "gzip")]
+    pub gzip: bool,
+    #[cfg(feature = "snap")]
+    pub snap_files: bool,
+}
+
+// Looks up a per-test setting. Settings are keyed by the path that was passed
+// when registering them, which may be the test's own path or a glob pattern
+// matching it. Like test paths, relative keys are resolved against `base_dir`.
+pub fn for_test<'a, T>(
+    settings: &'a Map<PathBuf, T>,
+    path: &Path,
+    base_dir: Option<&Path>,
+) -> Option<&'a T> {
+    let resolve = |key: &'a Path| match base_dir {
+        Some(base_dir) => Cow::Owned(base_dir.join(key)),
+        None => Cow::Borrowed(key),
+    };
+
+    if let Some((_, setting)) = settings.iter().find(|(key, _)| resolve(key) == path) {
+        return Some(setting);
+    }
+
+    settings.iter().find_map(|(key, setting)| {
+        let key = resolve(key);
+        let pattern = Pattern::new(key.to_str()?).ok()?;
+        if pattern.matches_path(path) {
+            Some(setting)
+        } else {
+            None
+        }
+    })
+}
+
+#[derive(Clone)]
+pub struct Normalizer(pub Rc<dyn Fn(&str) -> String>);
+
+impl Debug for Normalizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Normalizer")
+    }
+}
+
+#[derive(Clone)]
+pub struct Condition(pub Rc<dyn Fn(&Environment) -> bool>);
+
+impl Debug for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Condition")
+    }
+}
+
+/// What a condition given to `TestCases::compile_fail_if` is checked against.
+pub struct Environment<'a> {
+    pub(crate) project: &'a Project,
+}
+
+impl Environment<'_> {
+    /// Features of the crate under test that the tests are built with, or
+    /// `None` if they could not be determined and cargo's defaults apply.
+    pub fn features(&self) -> Option<&[String]> {
+        self.project.features.as_deref()
+    }
+}
+
+#[derive(Clone)]
+pub struct ManifestEditor(pub Rc<dyn Fn(&mut Manifest)>);
+
+impl Debug for ManifestEditor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ManifestEditor")
+    }
+}
@@ -1,104 +1,457 @@
-use std::env;
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
 use std::fs::{self, File};
-use std::path::{Path, PathBuf};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::iter::Peekable;
+use std::path::{Component, Path, PathBuf};
 use std::process::Output;
+use std::str;
 
 use super::{TestKind, Runner, Test};
-use crate::cargo::{prepare_project, Project};
-use crate::env::Update;
+use crate::cargo::{self, prepare_project, Project};
+use crate::env::{self, Update};
 use crate::error::{Error, Result};
 use crate::message::{self, Fail, Warn};
 use crate::normalize::{self, Variations};
+use crate::options::{self, Environment, Options};
+use crate::sarif;
+use crate::snapshot;
+use crate::term;
+use crate::wildcard;
 
 use crate::TestRunner;
 
+// The tests a run is made of, worked out without printing anything.
+struct Selection {
+    // Every test the globs expand to, before any filtering.
+    discovered: Vec<Test>,
+    // The number of tests registered after glob expansion, counting paths
+    // that failed to expand.
+    expanded: usize,
+    // The tests to run, in the order to run them in.
+    tests: Vec<Test>,
+    // Paths that failed to expand, reported as failed tests.
+    bad_tests: Vec<(Test, Error)>,
+    // Globs that matched no files, which are warned about.
+    empty_globs: Vec<String>,
+    // The command line filters, to mention if they leave no tests.
+    filters: Vec<String>,
+    shard: Option<(usize, usize)>,
+    seed: Option<u64>,
+    // How many tests are left by TRYBUILD_RERUN_FAILED, if it applies.
+    rerun: Option<usize>,
+}
+
 impl<R: TestRunner> Runner<R> {
+    pub fn discover(&self) -> Vec<Test> {
+        self.select().map_or_else(|_| Vec::new(), |selection| selection.tests)
+    }
+
+    fn select(&self) -> Result<Selection> {
+        let (mut tests, mut bad_tests, empty_globs) = expand_globs(&self.tests, &self.options);
+        let discovered = tests.clone();
+        let expanded = tests.len() + bad_tests.len();
+
+        let mut filters = filter(&mut tests);
+        if let Some(kind) = filter_kind(&mut tests)? {
+            filters.push(format!("trybuild-kind={}", kind));
+        }
+
+        let shard = env::shard()?;
+        if let Some(shard) = shard {
+            take_shard(&mut tests, shard);
+            // Paths that failed to expand are the same in every shard, and
+            // are reported by the first one only.
+            if shard.0 != 1 {
+                bad_tests.clear();
+            }
+        }
+
+        let seed = env::seed()?;
+        if let Some(seed) = seed {
+            shuffle(&mut tests, seed);
+        }
+
+        let mut rerun = None;
+        if env::enabled("TRYBUILD_RERUN_FAILED") {
+            let failed = cargo::project_dir(&self.options)
+                .ok()
+                .and_then(|dir| load_failed(&failed_record(&dir, &self.tests)));
+            if let Some(failed) = failed {
+                if tests.iter().any(|test| failed.contains(&test.path)) {
+                    tests.retain(|test| failed.contains(&test.path));
+                    rerun = Some(tests.len());
+                }
+            }
+        }
+
+        Ok(Selection {
+            discovered,
+            expanded,
+            tests,
+            bad_tests,
+            empty_globs,
+            filters,
+            shard,
+            seed,
+            rerun,
+        })
+    }
+
     pub fn run(&mut self) {
-        let (mut tests, bad_tests) = expand_globs(&self.tests);
-        filter(&mut tests);
+        let selection = self.select().unwrap_or_else(|err| {
+            message::prepare_fail(err);
+            panic!("tests failed");
+        });
+        let Selection {
+            discovered,
+            expanded,
+            tests,
+            bad_tests,
+            empty_globs,
+            filters,
+            shard,
+            seed,
+            rerun,
+        } = selection;
+
+        for pattern in &empty_globs {
+            message::empty_glob(pattern);
+        }
+
+        if let Some(expected) = self.options.expected_count {
+            if expanded != expected {
+                message::prepare_fail(Error::TestCount(expected, expanded));
+                panic!("tests failed");
+            }
+        }
+        // With TRYBUILD_PRUNE, orphans are deleted after the run instead.
+        if self.options.check_orphans && !env::enabled("TRYBUILD_PRUNE") {
+            let orphans = snapshot::orphans(&self.options, &discovered);
+            if !orphans.is_empty() {
+                message::prepare_fail(Error::Orphans(orphans));
+                panic!("tests failed");
+            }
+        }
+
+        if env::enabled("TRYBUILD_LIST") {
+            message::list(&tests, &bad_tests, &self.options);
+            if !bad_tests.is_empty() {
+                panic!("tests failed");
+            }
+            return;
+        }
+
+        if tests.is_empty() && !filters.is_empty() && bad_tests.is_empty() {
+            message::no_tests_matched(&filters);
+            return;
+        }
+
+        let project = prepare_project(&tests, &self.options).unwrap_or_else(|err| {
+            message::prepare_fail(err);
+            if env::enabled("TRYBUILD_TAP") {
+                message::tap_bail_out();
+            }
+            panic!("tests failed");
+        });
+
+        let base_dir = self.options.base_dir.as_deref();
+        let skipped: Vec<String> = tests
+            .iter()
+            .filter(|test| match options::for_test(&self.options.conditions, &test.path, base_dir) {
+                Some(condition) => !(condition.0)(&Environment { project: &project }),
+                None => false,
+            })
+            .map(|test| test.name.clone())
+            .collect();
+        let enabled: Vec<Test> = tests
+            .iter()
+            .filter(|test| !skipped.contains(&test.name))
+            .cloned()
+            .collect();
+
+        let total = bad_tests.len() + tests.len();
+        let tap = env::enabled("TRYBUILD_TAP");
+        // TAP output is already limited to the results, so it takes precedence.
+        let quiet = env::enabled("TRYBUILD_QUIET") && !tap;
+        if tap {
+            term::capture();
+            message::tap_plan(total);
+        }
+
+        let sarif = env::sarif();
+        if sarif.is_some() {
+            term::record();
+        }
 
         let mut failures = 0;
+        let mut failed = Vec::new();
+        let mut sarif_failures = Vec::new();
+        let mut groups = Vec::new();
+        let mut number = 0;
         for (test, error) in bad_tests {
-            message::begin_test(&test, false);
+            number += 1;
+            tally(&mut groups, &test, false);
+            let description = test.description(&self.options);
+            message::begin_test(&project, &test, description, false, (number, total));
             message::test_fail(error);
             failures += 1;
+            failed.push(test.path.clone());
+            if sarif.is_some() {
+                let message = term::take_recorded();
+                sarif_failures.push(sarif::Failure { test: test.clone(), message });
+            }
+            if tap {
+                message::tap_result(number, &test, description, false);
+            }
         }
 
-        let (project, _) = prepare_project(&tests).unwrap_or_else(|err| {
-            message::prepare_fail(err);
-            panic!("tests failed");
-        });
+        if tests.is_empty() && !filters.is_empty() {
+            message::no_tests_matched(&filters);
+            write_sarif(sarif.as_deref(), &failed, &sarif_failures);
+            if failures > 0 {
+                panic!("{} of {} tests failed", failures, failures);
+            }
+            return;
+        }
 
-        self.runner.prepare(&tests).unwrap_or_else(|err| {
+        self.runner.prepare_in(&project, &enabled).unwrap_or_else(|err| {
             message::prepare_fail(Error::External(err.to_string()));
+            if tap {
+                message::tap_bail_out();
+            }
             panic!("tests failed");
         });
 
         print!("\n\n");
 
+        if let Some(shard) = shard {
+            message::shard(shard);
+        }
+
+        if let Some(seed) = seed {
+            message::shuffled(seed);
+        }
+
+        if let Some(count) = rerun {
+            message::rerun_failed(count);
+        }
+
+        if env::enabled("TRYBUILD_VERBOSE") {
+            message::features(&project);
+        }
+
+        if !project.dropped_features.is_empty() {
+            message::dropped_features(&project.dropped_features);
+        }
+
         if tests.is_empty() {
             message::no_tests_enabled();
         } else {
+            let mut group = None;
             for test in &tests {
-                if let Err(e) = self.run_one(test, &project) {
+                number += 1;
+                if test.group.is_some() && test.group != group && !quiet && !tap {
+                    message::group(test.group.as_deref().unwrap_or_default());
+                }
+                group = test.group.clone();
+
+                // Only what is printed about each test goes in its SARIF result.
+                term::take_recorded();
+                if skipped.contains(&test.name) {
+                    if !quiet {
+                        let description = test.description(&self.options);
+                        let progress = (number, total);
+                        message::begin_test(&project, test, description, show_kind(&project), progress);
+                        message::skipped();
+                    }
+                    if tap {
+                        message::tap_skipped(number, test);
+                    }
+                    continue;
+                }
+
+                if quiet {
+                    term::hold();
+                }
+                let result = self.run_one(test, &project, (number, total));
+                let ok = result.is_ok();
+                tally(&mut groups, test, ok);
+                if let Err(e) = result {
                     message::test_fail(e);
                     failures += 1;
+                    failed.push(test.path.clone());
+                }
+                if sarif.is_some() {
+                    let message = term::take_recorded();
+                    if !ok {
+                        sarif_failures.push(sarif::Failure { test: test.clone(), message });
+                    }
+                }
+                if quiet {
+                    if ok {
+                        term::discard();
+                    } else {
+                        term::release();
+                    }
+                }
+                if tap {
+                    message::tap_result(number, test, test.description(&self.options), ok);
                 }
             }
         }
 
         print!("\n\n");
 
+        if !groups.is_empty() && !tap {
+            message::group_summary(&groups);
+        }
+
+        if quiet || !skipped.is_empty() {
+            message::summary(total, failures, skipped.len());
+        }
+
+        record_failed(&failed_record(&project.dir, &self.tests), &enabled, &failed);
+
+        if failures == 0 && (self.options.check_orphans || env::enabled("TRYBUILD_PRUNE")) {
+            prune(&project, &discovered, &enabled);
+        }
+
+        let mut ran: Vec<PathBuf> = enabled.iter().map(|test| test.path.clone()).collect();
+        ran.extend(failed);
+        write_sarif(sarif.as_deref(), &ran, &sarif_failures);
+
         if failures > 0 && project.name != "trybuild-tests" {
-            panic!("{} of {} tests failed", failures, tests.len());
+            match shard {
+                Some((k, n)) => panic!(
+                    "{} of {} tests in shard {}/{} failed",
+                    failures,
+                    tests.len(),
+                    k,
+                    n,
+                ),
+                None => panic!("{} of {} tests failed", failures, tests.len()),
+            }
         }
     }
 
-    fn run_one(&mut self, test: &Test, project: &Project) -> Result<()> {
-        let num_kinds = (project.has_pass as u8)
-            + (project.has_compile_fail as u8)
-            + (project.has_output as u8);
+    fn run_one(
+        &mut self,
+        test: &Test,
+        project: &Project,
+        progress: (usize, usize),
+    ) -> Result<()> {
+        let show_expected = show_kind(project);
+        let description = test.description(&project.options);
+        message::begin_test(project, test, description, show_expected, progress);
+        if test.inline.is_none() {
+            check_exists(&test.path)?;
+        }
 
-        let show_expected = num_kinds > 1;
-        message::begin_test(test, show_expected);
-        check_exists(&test.path)?;
+        if project.stream {
+            println!();
+        }
 
         let output = self.runner.build(test)
             .map_err(|e| Error::External(e.to_string()))?;
 
+        if project.stream {
+            return test.check_streamed(&output);
+        }
+
         let build_stderr = normalize::diagnostics(&output.stderr, test, project);
+        let warnings = if env::enabled("TRYBUILD_VERBOSE") {
+            normalize::warning_count(build_stderr.preferred())
+        } else {
+            0
+        };
+
+        if project.options.check_reproducible && matches!(test.kind, TestKind::CompileFail) {
+            let again = self.runner.build(test)
+                .map_err(|e| Error::External(e.to_string()))?;
+            let again = normalize::diagnostics(&again.stderr, test, project);
+            if again.preferred() != build_stderr.preferred() {
+                message::not_reproducible(project, build_stderr.preferred(), again.preferred());
+                return Err(Error::NotReproducible);
+            }
+        }
+
         let check = match test.kind {
             TestKind::Pass => Test::check_pass,
             TestKind::CompileFail => Test::check_compile_fail,
             TestKind::Output => Test::check_output,
         };
 
-        check(test, &mut self.runner, project, output, build_stderr)
+        check(test, &mut self.runner, project, output, build_stderr, warnings)
     }
 }
 
 impl Test {
+    fn description<'a>(&self, options: &'a Options) -> Option<&'a str> {
+        let base_dir = options.base_dir.as_deref();
+        options::for_test(&options.descriptions, &self.path, base_dir).map(String::as_str)
+    }
+
+    // Where the expected stderr is kept: next to the test, with the extension
+    // `stderr` unless configured otherwise.
+    pub(crate) fn stderr_path(&self, options: &Options) -> PathBuf {
+        let ext = options.stderr_ext.as_deref().unwrap_or("stderr");
+        self.path.with_extension(ext)
+    }
+
+    // The build output went straight to the terminal, so there is nothing to
+    // compare against; only whether the test compiled is checked.
+    fn check_streamed(&self, build_output: &Output) -> Result<()> {
+        let compiled = build_output.status.success();
+        match self.kind {
+            TestKind::CompileFail if compiled => {
+                message::should_not_have_compiled();
+                Err(Error::ShouldNotHaveCompiled)
+            }
+            TestKind::Pass | TestKind::Output if !compiled => Err(Error::BuildFail),
+            _ => {
+                message::ok();
+                Ok(())
+            }
+        }
+    }
+
     fn check_pass<R: TestRunner>(
         &self,
         runner: &mut R,
-        _project: &Project,
+        project: &Project,
         build_output: Output,
         variations: Variations,
+        warnings: usize,
     ) -> Result<()> {
         let preferred = variations.preferred();
         if !build_output.status.success() {
-            message::failed_to_build(preferred);
+            message::failed_to_build(project, preferred);
             return Err(Error::CargoFail);
         }
 
-        let mut output = runner.run(self)
-            .map_err(|e| Error::External(e.to_string()))?;
+        let (mut output, attempts) = self.run_with_retries(runner, project, |_, output| {
+            Ok(output.status.success())
+        })?;
 
         output.stdout.splice(..0, build_output.stdout);
-        message::output(preferred, &output);
+        let base_dir = project.options.base_dir.as_deref();
+        let silent = options::for_test(&project.options.silent, &self.path, base_dir).is_some();
+        if silent && output.status.success() && !is_silent(&output) {
+            message::not_silent(project, preferred, &output);
+            return Err(Error::NotSilent);
+        }
+
+        message::output(project, preferred, warnings, &output);
+        if attempts > 1 && output.status.success() {
+            message::passed_after_retry(attempts);
+        }
         if output.status.success() {
             Ok(())
+        } else if let Some(signal) = message::killed_by(&output.status) {
+            Err(Error::Signal(signal))
         } else {
             Err(Error::RunFailed)
         }
@@ -110,154 +463,541 @@ impl Test {
         project: &Project,
         build_output: Output,
         variations: Variations,
+        warnings: usize,
     ) -> Result<()> {
         let preferred = variations.preferred();
         if !build_output.status.success() {
-            message::failed_to_build(preferred);
+            message::failed_to_build(project, preferred);
             return Err(Error::BuildFail);
         }
 
-        let output = runner.run(self)
-            .map_err(|e| Error::External(e.to_string()))?;
+        let stderr_path = self.stderr_path(&project.options);
+        let stdout_path = self.path.with_extension("stdout");
+
+        let (output, attempts) = self.run_with_retries(runner, project, |runner, output| {
+            Ok(output_matches(self, runner, project, &stderr_path, &output.stderr)?
+                && output_matches(self, runner, project, &stdout_path, &output.stdout)?)
+        })?;
 
         println!(); println!();
-        let stderr_path = self.path.with_extension("stderr");
         message::output_prefix("stderr");
-        check_output(self, project, &stderr_path, false, &output.stderr)?;
+        check_output(self, runner, project, &stderr_path, false, &output.stderr, warnings)?;
 
-        let stdout_path = self.path.with_extension("stdout");
         message::output_prefix("stdout");
-        check_output(self, project, &stdout_path, false, &output.stdout).map(|_| ())?;
+        check_output(self, runner, project, &stdout_path, false, &output.stdout, 0).map(|_| ())?;
+
+        if attempts > 1 {
+            message::passed_after_retry(attempts);
+        }
 
         println!();
         Ok(())
     }
 
+    // Runs the test binary, running it again while `succeeded` says it did not
+    // as long as the test has retries left. Returns the last output and the
+    // number of attempts made.
+    fn run_with_retries<R, F>(
+        &self,
+        runner: &mut R,
+        project: &Project,
+        succeeded: F,
+    ) -> Result<(Output, usize)>
+    where
+        R: TestRunner,
+        F: Fn(&R, &Output) -> Result<bool>,
+    {
+        let base_dir = project.options.base_dir.as_deref();
+        let retries = options::for_test(&project.options.flaky, &self.path, base_dir)
+            .copied()
+            .unwrap_or(0);
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let output = runner.run(self)
+                .map_err(|e| Error::External(e.to_string()))?;
+            if attempts > retries || succeeded(runner, &output)? {
+                return Ok((output, attempts));
+            }
+        }
+    }
+
     fn check_compile_fail<R: TestRunner>(
         &self,
-        _runner: &mut R,
+        runner: &mut R,
         project: &Project,
         build_output: Output,
         variations: Variations,
+        warnings: usize,
     ) -> Result<()> {
         let preferred = variations.preferred();
 
         if build_output.status.success() {
             message::should_not_have_compiled();
-            message::fail_output(Fail, &build_output.stdout);
-            message::warnings(preferred);
+            message::fail_output(project, Fail, &build_output.stdout);
+            message::warnings(project, preferred);
             return Err(Error::ShouldNotHaveCompiled);
         }
 
+        if project.options.require_stderr && preferred.is_empty() {
+            return Err(Error::EmptyStderr);
+        }
+
+        let base_dir = project.options.base_dir.as_deref();
+        let error_count = options::for_test(&project.options.error_count, &self.path, base_dir);
+        if let Some(&expected) = error_count {
+            let actual = normalize::error_count(preferred);
+            if actual != expected {
+                return Err(Error::MismatchedCount(expected, actual));
+            }
+        }
+
+        let contains = options::for_test(&project.options.contains, &self.path, base_dir);
+        for text in contains.into_iter().flatten() {
+            if !variations.any(|stderr| stderr.contains(text.as_str())) {
+                message::lacks_required(project, text, preferred);
+                return Err(Error::LacksRequired(text.clone()));
+            }
+        }
+
+        let not_contains = options::for_test(&project.options.not_contains, &self.path, base_dir);
+        for text in not_contains.into_iter().flatten() {
+            if variations.any(|stderr| stderr.contains(text.as_str())) {
+                message::contains_forbidden(project, text, preferred);
+                return Err(Error::ContainsForbidden(text.clone()));
+            }
+        }
+
+        if let Some(inline) = &self.inline {
+            let expected = format!("{}\n", inline.expected.trim_start_matches('\n').trim_end());
+            if !runner.compare(&expected, &variations) {
+                message::mismatch(project, &expected, preferred);
+                return Err(Error::Mismatch);
+            }
+            message::ok_with_warnings(warnings);
+            message::fail_output(project, Warn, &build_output.stdout);
+            return Ok(());
+        }
+
+        if project.options.separate_warnings {
+            return self.check_compile_fail_warnings(runner, project, &build_output, warnings);
+        }
+
         // FIXME: This is different than what was here before...
         // Before, it used `preferred`, now, it uses stderr directly.
-        let stderr_path = self.path.with_extension("stderr");
-        match check_output(self, project, &stderr_path, false, &build_output.stderr) {
+        let stderr_path = self.stderr_path(&project.options);
+        let stderr = &build_output.stderr;
+        match check_output(self, runner, project, &stderr_path, false, stderr, warnings) {
             Ok(true) => {
-                message::fail_output(Warn, &build_output.stdout);
-                return Ok(());
+                message::fail_output(project, Warn, &build_output.stdout);
+                Ok(())
             }
             result => result.map(|_| ())
         }
     }
+
+    // The errors are compared against the usual stderr file and the warnings
+    // against their own `.warnings` file, like the two streams of an output
+    // test.
+    fn check_compile_fail_warnings<R: TestRunner>(
+        &self,
+        runner: &R,
+        project: &Project,
+        build_output: &Output,
+        warning_count: usize,
+    ) -> Result<()> {
+        let (warnings, errors) = normalize::split_warnings(&build_output.stderr);
+        let stderr_path = self.stderr_path(&project.options);
+        let warnings_path = self.path.with_extension("warnings");
+
+        println!(); println!();
+        message::output_prefix("stderr");
+        let errors_result = check_output(
+            self, runner, project, &stderr_path, false, errors.as_bytes(), warning_count,
+        );
+
+        message::output_prefix("warnings");
+        let warnings_result =
+            check_output(self, runner, project, &warnings_path, false, warnings.as_bytes(), 0);
+
+        println!();
+        errors_result?;
+        warnings_result?;
+        message::fail_output(project, Warn, &build_output.stdout);
+        Ok(())
+    }
 }
 
-fn check_output(
+fn check_output<R: TestRunner>(
     test: &Test,
+    runner: &R,
     project: &Project,
     path: &Path,
     must_exist: bool,
-    output: &[u8]
+    output: &[u8],
+    warnings: usize,
 ) -> Result<bool> {
-    let content = normalize::diagnostics(output, test, project);
-    if !path.exists() && (must_exist || !output.is_empty()) {
+    if is_binary(project, output) {
+        return check_bytes(project, path, must_exist, output, warnings);
+    }
+
+    let (expected, content) = prepare_comparison(test, project, path, output)?;
+    if expected.is_none() && (must_exist || !output.is_empty()) {
         make_wip(project, path, content.preferred())?;
         return Ok(true);
     }
 
-    let expected = if path.exists() {
-        let expected = fs::read_to_string(path)
-            .map_err(Error::ReadStderr)? // FIXME
-            .replace("\r\n", "\n");
+    let expected = match expected {
+        Some(expected) => {
+            if runner.compare(&expected, &content) {
+                message::ok_with_warnings(warnings);
+                return Ok(false);
+            }
 
-        if content.any(|v| expected == v) {
-            message::ok();
+            expected
+        }
+        None => {
+            message::ok_with_warnings(warnings);
             return Ok(false);
         }
-
-        expected
-    } else if output.is_empty() {
-        message::ok();
-        return Ok(false);
-    } else {
-        "".into()
     };
 
     let actual = content.preferred();
     match project.update {
         Update::Wip => {
-            message::mismatch(&expected, actual);
+            message::mismatch(project, &expected, actual);
+            if env::enabled("TRYBUILD_VERBOSE") {
+                message::raw_output(project, &String::from_utf8_lossy(output));
+                message::variations(project, &expected, &content);
+            }
+            report_changed_failure(test, &expected, actual);
+            snapshot::write_pending(project, path, actual)?;
             Err(Error::Mismatch)
         }
         Update::Overwrite => {
-            message::overwrite(path, actual);
-            fs::write(path, actual).map_err(Error::WriteStderr)?; // FIXME
+            let updated = wildcard::preserve(&expected, actual);
+            message::overwrite_diff(project, path, &expected, &updated);
+            report_changed_failure(test, &expected, actual);
+            snapshot::write(project, path, &updated)?;
+            Ok(false)
+        }
+    }
+}
+
+// Points out a compile-fail test that no longer fails with any of the errors
+// it is expected to, as opposed to one whose error messages were reworded.
+fn report_changed_failure(test: &Test, expected: &str, actual: &str) {
+    if let TestKind::CompileFail = test.kind {
+        let expected = normalize::error_kinds(expected);
+        let actual = normalize::error_kinds(actual);
+        let related = expected
+            .iter()
+            .any(|expected| actual.iter().any(|actual| wildcard::matches(expected, actual)));
+        if !expected.is_empty() && !actual.is_empty() && !related {
+            message::failure_changed(&expected, &actual);
+        }
+    }
+}
+
+// Whether `output` would pass `check_output`, without printing anything. Output
+// with no expected file yet counts as matching, since it becomes the new
+// expected output.
+fn output_matches<R: TestRunner>(
+    test: &Test,
+    runner: &R,
+    project: &Project,
+    path: &Path,
+    output: &[u8],
+) -> Result<bool> {
+    if is_binary(project, output) {
+        let expected = snapshot::read_bytes(&project.options, path)?;
+        return Ok(expected.map_or(true, |expected| expected == output));
+    }
+
+    let (expected, content) = prepare_comparison(test, project, path, output)?;
+    Ok(match expected {
+        Some(expected) => runner.compare(&expected, &content),
+        None => true,
+    })
+}
+
+// Output that is not valid UTF-8 is compared byte for byte, without any
+// normalization, since a lossy conversion would hide exactly the bytes that
+// differ. Only standalone expected files can hold such output; other snapshot
+// stores fall back to comparing the lossy text.
+fn is_binary(project: &Project, output: &[u8]) -> bool {
+    str::from_utf8(output).is_err() && snapshot::standalone(project)
+}
+
+fn check_bytes(
+    project: &Project,
+    path: &Path,
+    must_exist: bool,
+    output: &[u8],
+    warnings: usize,
+) -> Result<bool> {
+    let expected = snapshot::read_bytes(&project.options, path)?;
+    let path = &snapshot::mirror(&project.options, path);
+
+    match expected {
+        Some(expected) if expected == output => {
+            message::ok_with_warnings(warnings);
+            Ok(false)
+        }
+        Some(expected) => match project.update {
+            Update::Wip => {
+                message::mismatch_bytes(project, &expected, output);
+                Err(Error::Mismatch)
+            }
+            Update::Overwrite => {
+                let previous = message::hex_dump(&expected);
+                message::overwrite_diff(project, path, &previous, &message::hex_dump(output));
+                fs::write(path, output).map_err(Error::WriteStderr)?;
+                Ok(false)
+            }
+        },
+        None if must_exist || !output.is_empty() => {
+            make_wip_bytes(project, path, output)?;
+            Ok(true)
+        }
+        None => {
+            message::ok_with_warnings(warnings);
             Ok(false)
         }
     }
 }
 
+// Reads the expected output for `path` and normalizes the actual output, in
+// the form in which the two are compared.
+fn prepare_comparison(
+    test: &Test,
+    project: &Project,
+    path: &Path,
+    output: &[u8],
+) -> Result<(Option<String>, Variations)> {
+    let mut content = normalize::diagnostics(output, test, project);
+    let mut expected = snapshot::read(project, path)?;
+
+    let base_dir = project.options.base_dir.as_deref();
+    if options::for_test(&project.options.unordered, &test.path, base_dir).is_some() {
+        content.sort_lines();
+        expected = expected.map(|expected| normalize::sort_lines(&expected));
+    }
+
+    Ok((expected, content))
+}
+
+// Whether to say what is expected of each test, which is only worth it in a
+// suite with tests of more than one kind.
+fn show_kind(project: &Project) -> bool {
+    let num_kinds = (project.has_pass as u8)
+        + (project.has_compile_fail as u8)
+        + (project.has_output as u8);
+    num_kinds > 1
+}
+
+// Counts the passed and failed tests of each group, in the order the groups
+// are first run.
+fn tally(groups: &mut Vec<(String, usize, usize)>, test: &Test, ok: bool) {
+    let name = match &test.group {
+        Some(name) => name,
+        None => return,
+    };
+    let index = match groups.iter().position(|(group, _, _)| group == name) {
+        Some(index) => index,
+        None => {
+            groups.push((name.clone(), 0, 0));
+            groups.len() - 1
+        }
+    };
+    if ok {
+        groups[index].1 += 1;
+    } else {
+        groups[index].2 += 1;
+    }
+}
+
+// The tests that failed in the last run, for TRYBUILD_RERUN_FAILED, are kept
+// in the test project directory, one path per line. Every `TestCases` in the
+// crate shares that directory, so each has a record of its own, named after
+// the paths it registers. There is no record once all of its tests have
+// passed, so that the next rerun goes back to running everything.
+fn failed_record(dir: &Path, tests: &[Test]) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    for test in tests {
+        test.path.hash(&mut hasher);
+    }
+    dir.join(format!("failed-tests-{:016x}", hasher.finish()))
+}
+
+fn load_failed(record: &Path) -> Option<Vec<PathBuf>> {
+    let record = fs::read_to_string(record).ok()?;
+    Some(record.lines().map(PathBuf::from).collect())
+}
+
+// Tests that were not run this time, because of a filter or a shard, keep
+// their place in the record.
+fn record_failed(record: &Path, ran: &[Test], failed: &[PathBuf]) {
+    let mut paths = load_failed(record).unwrap_or_default();
+    paths.retain(|path| !ran.iter().any(|test| test.path == *path));
+    paths.extend(failed.iter().cloned());
+    paths.sort();
+    paths.dedup();
+
+    if paths.is_empty() {
+        let _ = fs::remove_file(record);
+        return;
+    }
+
+    let mut content = String::new();
+    for path in &paths {
+        content += &path.to_string_lossy();
+        content.push('\n');
+    }
+    let _ = fs::write(record, content);
+}
+
+// Creates the wip directory along with a .gitignore for everything in it,
+// unless there is one already, which may have been customized.
+fn wip_dir() -> Result<&'static Path> {
+    let wip_dir = Path::new("wip");
+    fs::create_dir_all(wip_dir)?;
+    let gitignore_path = wip_dir.join(".gitignore");
+    if !gitignore_path.exists() {
+        fs::write(gitignore_path, "*\n")?;
+    }
+    Ok(wip_dir)
+}
+
+// Moves the output of the last run aside to `.prev`, so that successive wip
+// outputs of a test can be compared.
+fn keep_previous_wip(project: &Project, wip_path: &Path) -> Result<()> {
+    if project.options.keep_previous_wip && wip_path.exists() {
+        let mut prev = wip_path.as_os_str().to_owned();
+        prev.push(".prev");
+        fs::rename(wip_path, prev).map_err(Error::WriteStderr)?;
+    }
+    Ok(())
+}
+
 fn make_wip(project: &Project, path: &Path, content: &str) -> Result<()> {
     let ext = path.extension().expect("wip path has extension");
     match project.update {
         Update::Wip => {
-            let wip_dir = Path::new("wip");
-            fs::create_dir_all(wip_dir)?;
-            let gitignore_path = wip_dir.join(".gitignore");
-            fs::write(gitignore_path, "*\n")?;
-
-            let default = Path::new("test").with_extension(ext);
-            let name = path.file_name()
-                .unwrap_or_else(|| default.as_os_str());
-            let wip_path = wip_dir.join(name);
-            message::write_wip(&wip_path, &path, content);
-            fs::write(wip_path, content).map_err(Error::WriteStderr)?;
+            if let Some(pending) = snapshot::write_pending(project, path, content)? {
+                message::write_wip(project, &pending, &pending.with_extension("snap"), content);
+                return Ok(());
+            }
+
+            let wip_dir = wip_dir()?;
+
+            if let Some(file) = &project.options.snapshot_file {
+                // Start from the previous wip copy if this run already made
+                // one, so that it collects every new entry.
+                let wip_path = wip_dir.join(file.file_name().expect("snapshot file name"));
+                let src = if wip_path.exists() { &wip_path } else { file };
+                message::write_wip(project, &wip_path, file, content);
+                return snapshot::update(&project.options, src, &wip_path, path, content);
+            }
+
+            let stored = snapshot::file_path(&project.options, path);
+            let wip_path = wip_path(wip_dir, &stored, ext);
+            message::write_wip(project, &wip_path, &stored, content);
+            keep_previous_wip(project, &wip_path)?;
+            snapshot::write_file(&wip_path, &snapshot::with_header(project, content))?;
         }
         Update::Overwrite => {
-            message::overwrite(&path, content);
-            fs::write(path, content).map_err(Error::WriteStderr)?;
+            message::overwrite(project, path, content);
+            snapshot::write(project, path, content)?;
         }
     }
 
     Ok(())
 }
 
-fn check_exists(path: &Path) -> Result<()> {
-    if path.exists() {
-        return Ok(());
+// Binary output is saved the way `make_wip` saves text, with a hex dump of it
+// in the message, but always to a standalone file.
+fn make_wip_bytes(project: &Project, path: &Path, output: &[u8]) -> Result<()> {
+    let dump = message::hex_dump(output);
+    match project.update {
+        Update::Wip => {
+            let ext = path.extension().expect("wip path has extension");
+            let wip_path = wip_path(wip_dir()?, path, ext);
+            message::write_wip(project, &wip_path, path, &dump);
+            keep_previous_wip(project, &wip_path)?;
+            fs::write(wip_path, output).map_err(Error::WriteStderr)
+        }
+        Update::Overwrite => {
+            message::overwrite(project, path, &dump);
+            fs::write(path, output).map_err(Error::WriteStderr)
+        }
     }
-    match File::open(path) {
-        Ok(_) => Ok(()),
-        Err(err) => Err(Error::Open(path.to_owned(), err)),
+}
+
+fn wip_path(wip_dir: &Path, stored: &Path, ext: &OsStr) -> PathBuf {
+    let default = Path::new("test").with_extension(ext);
+    let name = stored.file_name()
+        .unwrap_or_else(|| default.as_os_str());
+    wip_dir.join(name)
+}
+
+fn is_silent(output: &Output) -> bool {
+    normalize::trim(&output.stdout).is_empty() && normalize::trim(&output.stderr).is_empty()
+}
+
+fn check_exists(path: &Path) -> Result<()> {
+    let error = match fs::metadata(path) {
+        Ok(metadata) if metadata.is_dir() => return Err(Error::IsDirectory(path.to_owned())),
+        Ok(_) => match File::open(path) {
+            Ok(_) => return Ok(()),
+            Err(error) => error,
+        },
+        Err(error) => error,
+    };
+
+    match error.kind() {
+        io::ErrorKind::NotFound => Err(Error::Missing(path.to_owned())),
+        io::ErrorKind::PermissionDenied => Err(Error::PermissionDenied(path.to_owned())),
+        _ => Err(Error::Open(path.to_owned(), error)),
     }
 }
 
-fn expand_globs(tests: &[Test]) -> (Vec<Test>, Vec<(Test, Error)>) {
+// Relative test paths are resolved against `base_dir` if one is set, and are
+// otherwise left relative to the working directory. Globs that match nothing
+// are returned as well, to be warned about, as they are usually a typo or a
+// moved directory.
+fn expand_globs(
+    tests: &[Test],
+    options: &Options,
+) -> (Vec<Test>, Vec<(Test, Error)>, Vec<String>) {
     fn glob(pattern: &str) -> Result<Vec<PathBuf>> {
         let mut paths = glob::glob(pattern)?
             .map(|entry| entry.map_err(Error::from))
             .collect::<Result<Vec<PathBuf>>>()?;
-        paths.sort();
+        paths.sort_by(|a, b| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()));
         Ok(paths)
     }
 
     let mut expanded_tests = Vec::new();
     let mut bad_tests = Vec::new();
+    let mut empty_globs = Vec::new();
 
     for test in tests {
+        let mut test = test.clone();
+        if test.inline.is_some() {
+            expanded_tests.push(test);
+            continue;
+        }
+
+        if let Some(base_dir) = &options.base_dir {
+            test.path = base_dir.join(&test.path);
+        }
+
         if let Some(utf8) = test.path.to_str() {
             if utf8.contains('*') {
                 match glob(utf8) {
+                    Ok(paths) if paths.is_empty() => empty_globs.push(utf8.to_owned()),
                     Ok(paths) => {
                         for path in paths {
                             let num = expanded_tests.len();
@@ -265,21 +1005,146 @@ fn expand_globs(tests: &[Test]) -> (Vec<Test>, Vec<(Test, Error)>) {
                             expanded_tests.push(Test {
                                 name,
                                 path,
-                                kind: test.kind
+                                kind: test.kind,
+                                inline: None,
+                                group: test.group.clone(),
                             });
                         }
                     }
                     Err(error) => {
-                        bad_tests.push((test.clone(), error));
+                        bad_tests.push((test, error));
                     }
                 }
             } else {
-                expanded_tests.push(test.clone());
+                expanded_tests.push(test);
+            }
+        }
+    }
+
+    if !options.allow_outside_paths {
+        let source_dir = options
+            .manifest_dir
+            .clone()
+            .or_else(|| std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from))
+            .unwrap_or_default();
+        let (inside, outside) = expanded_tests
+            .into_iter()
+            .partition(|test| test.inline.is_some() || is_within(&source_dir, &test.path));
+        expanded_tests = inside;
+        for test in outside {
+            let error = Error::OutsidePath(test.path.clone());
+            bad_tests.push((test, error));
+        }
+    }
+
+    (expanded_tests, bad_tests, empty_globs)
+}
+
+// Test files are built from `source_dir.join(path)`. This only looks at the
+// path itself, so `..` components are resolved without following symlinks.
+fn is_within(source_dir: &Path, path: &Path) -> bool {
+    fn lexical(path: &Path) -> Option<PathBuf> {
+        let mut normalized = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !normalized.pop() {
+                        return None;
+                    }
+                }
+                component => normalized.push(component),
             }
         }
+        Some(normalized)
     }
 
-    (expanded_tests, bad_tests)
+    match (lexical(source_dir), lexical(&source_dir.join(path))) {
+        (Some(source_dir), Some(path)) => path.starts_with(source_dir),
+        _ => false,
+    }
+}
+
+// Orders runs of digits by their numeric value so that `test2.rs` comes before
+// `test10.rs`, and everything else character by character.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+
+    loop {
+        match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                let x_value = x.trim_start_matches('0');
+                let y_value = y.trim_start_matches('0');
+                let ordering = x_value
+                    .len()
+                    .cmp(&y_value.len())
+                    .then_with(|| x_value.cmp(y_value))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.cmp(y);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_number<I: Iterator<Item = char>>(chars: &mut Peekable<I>) -> String {
+    let mut number = String::new();
+    while let Some(&ch) = chars.peek() {
+        if !ch.is_ascii_digit() {
+            break;
+        }
+        number.push(ch);
+        chars.next();
+    }
+    number
+}
+
+// Keeps the k-th of n contiguous, roughly equal parts of the tests, so that
+// every test lands in exactly one shard as long as all shards see the same
+// list of tests.
+fn take_shard(tests: &mut Vec<Test>, shard: (usize, usize)) {
+    let (k, n) = shard;
+    let len = tests.len();
+    let mut index = 0;
+    tests.retain(|_| {
+        let keep = index * n / len == k - 1;
+        index += 1;
+        keep
+    });
+}
+
+// Randomize the order of tests to shake out tests that only pass because of
+// something an earlier test did. Fisher-Yates driven by splitmix64, which is
+// plenty for this and avoids a dependency.
+fn shuffle(tests: &mut [Test], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+
+    for i in (1..tests.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        tests.swap(i, j);
+    }
 }
 
 // Filter which test cases are run by trybuild.
@@ -291,8 +1156,10 @@ fn expand_globs(tests: &[Test]) -> (Vec<Test>, Vec<(Test, Error)>) {
 // Cargo to run the test at all. The next argument starting with `trybuild=`
 // provides a filename filter. Only test cases whose filename contains the
 // filter string will be run.
-fn filter(tests: &mut Vec<Test>) {
-    let filters = env::args_os()
+//
+// Returns the filters that were applied, if any.
+fn filter(tests: &mut Vec<Test>) -> Vec<String> {
+    let filters = std::env::args_os()
         .flat_map(OsString::into_string)
         .filter_map(|mut arg| {
             const PREFIX: &str = "trybuild=";
@@ -305,7 +1172,7 @@ fn filter(tests: &mut Vec<Test>) {
         .collect::<Vec<String>>();
 
     if filters.is_empty() {
-        return;
+        return filters;
     }
 
     tests.retain(|t| {
@@ -313,4 +1180,118 @@ fn filter(tests: &mut Vec<Test>) {
             .iter()
             .any(|f| t.path.to_string_lossy().contains(f))
     });
+
+    filters
+}
+
+// Stops recording for the SARIF log and merges this run's results into it.
+fn write_sarif(path: Option<&Path>, ran: &[PathBuf], failures: &[sarif::Failure]) {
+    if let Some(path) = path {
+        term::stop_recording();
+        if let Err(err) = sarif::write(path, ran, failures) {
+            message::prepare_fail(err);
+        }
+    }
+}
+
+// Expected output files that no test read are deleted with TRYBUILD_PRUNE=1,
+// and otherwise only reported. Only the files of the tests that were run, and
+// those of no test at all, are considered, so filtered runs are safe.
+fn prune(project: &Project, discovered: &[Test], ran: &[Test]) {
+    let delete = env::enabled("TRYBUILD_PRUNE");
+    for file in snapshot::unused(&project.options, discovered, ran) {
+        if !delete {
+            message::unused(project, &file);
+        } else if let Err(err) = fs::remove_file(&file) {
+            message::prepare_fail(Error::Open(file, err));
+        } else {
+            message::pruned(project, &file);
+        }
+    }
+}
+
+// `trybuild-kind=compile_fail` on the command line runs only the tests of
+// that kind, on top of any `trybuild=` filter.
+fn filter_kind(tests: &mut Vec<Test>) -> Result<Option<String>> {
+    const PREFIX: &str = "trybuild-kind=";
+    let arg = match std::env::args_os()
+        .flat_map(OsString::into_string)
+        .find(|arg| arg.starts_with(PREFIX))
+    {
+        Some(arg) => arg[PREFIX.len()..].to_owned(),
+        None => return Ok(None),
+    };
+
+    let matches: fn(&TestKind) -> bool = match arg.as_str() {
+        "pass" => |kind| matches!(kind, TestKind::Pass),
+        "compile_fail" | "compile-fail" => |kind| matches!(kind, TestKind::CompileFail),
+        "output" => |kind| matches!(kind, TestKind::Output),
+        _ => return Err(Error::KindFilter(arg)),
+    };
+
+    tests.retain(|test| matches(&test.kind));
+    Ok(Some(arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_within, natural_cmp, take_shard};
+    use crate::{Test, TestKind};
+    use std::path::Path;
+
+    #[test]
+    fn natural_order() {
+        let mut paths = vec![
+            "tests/ui/test10.rs",
+            "tests/ui/test2.rs",
+            "tests/ui/test1.rs",
+            "tests/ui/test02.rs",
+            "tests/ui/other.rs",
+            "tests/ui/test2a.rs",
+        ];
+        paths.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(paths, [
+            "tests/ui/other.rs",
+            "tests/ui/test1.rs",
+            "tests/ui/test2.rs",
+            "tests/ui/test2a.rs",
+            "tests/ui/test02.rs",
+            "tests/ui/test10.rs",
+        ]);
+    }
+
+    #[test]
+    fn shards() {
+        let tests: Vec<Test> = (0..7)
+            .map(|n| Test {
+                name: Test::gen_name(n),
+                path: format!("tests/ui/{}.rs", n).into(),
+                kind: TestKind::Pass,
+                inline: None,
+                group: None,
+            })
+            .collect();
+
+        let mut seen = Vec::new();
+        for k in 1..=3 {
+            let mut shard = tests.clone();
+            take_shard(&mut shard, (k, 3));
+            assert!((2..=3).contains(&shard.len()));
+            seen.extend(shard.into_iter().map(|test| test.name));
+        }
+        assert_eq!(seen, tests.into_iter().map(|test| test.name).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn outside_paths() {
+        let source_dir = Path::new("/home/me/project");
+        assert!(is_within(source_dir, Path::new("tests/ui/a.rs")));
+        assert!(is_within(source_dir, Path::new("tests/../tests/ui/a.rs")));
+        assert!(is_within(source_dir, Path::new("/home/me/project/tests/ui/a.rs")));
+        assert!(!is_within(source_dir, Path::new("../other/tests/ui/a.rs")));
+        assert!(!is_within(source_dir, Path::new("tests/../../other/a.rs")));
+        assert!(!is_within(source_dir, Path::new("/home/me/other/a.rs")));
+        assert!(is_within(Path::new(""), Path::new("tests/ui/a.rs")));
+        assert!(!is_within(Path::new(""), Path::new("../a.rs")));
+    }
 }
@@ -1,10 +1,12 @@
+use crate::options::Options;
 use std::env;
+use std::ffi::OsString;
 use std::process::Command;
 
 const RUSTFLAGS: &str = "RUSTFLAGS";
 const IGNORED_LINTS: &[&str] = &["dead_code"];
 
-pub fn make_vec() -> Vec<String> {
+pub fn make_vec(options: &Options) -> Vec<String> {
     let mut rustflags = Vec::new();
 
     for &lint in IGNORED_LINTS {
@@ -12,17 +14,30 @@ pub fn make_vec() -> Vec<String> {
         rustflags.push(lint.to_owned());
     }
 
+    // A single job is asked for to make the output deterministic, which
+    // parallel codegen within a crate could still upset.
+    if options.jobs == Some(1) {
+        rustflags.push("-C".to_owned());
+        rustflags.push("codegen-units=1".to_owned());
+    }
+
     rustflags
 }
 
-pub fn set_env(cmd: &mut Command) {
+// RUSTFLAGS replaces the flags from .cargo/config rather than adding to them,
+// so once it is set, whether by the user or for the flags of a single test,
+// the flags from the config have to be repeated in it.
+pub fn set_env(cmd: &mut Command, options: &Options, test_flags: &[String]) {
     let mut rustflags = match env::var_os(RUSTFLAGS) {
         Some(rustflags) => rustflags,
-        None => return,
+        None if test_flags.is_empty() => return,
+        None => OsString::new(),
     };
 
-    for flag in make_vec() {
-        rustflags.push(" ");
+    for flag in make_vec(options).iter().chain(test_flags) {
+        if !rustflags.is_empty() {
+            rustflags.push(" ");
+        }
         rustflags.push(flag);
     }
 
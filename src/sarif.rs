@@ -0,0 +1,169 @@
+use crate::error::Result;
+use crate::{Test, TestKind};
+use serde_json::{json, Value};
+use lazy_static::lazy_static;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+// The tests of each kind are reported under a rule of their own, in this
+// order.
+const RULES: &[(&str, &str)] = &[
+    ("pass", "Test case is expected to compile and run successfully"),
+    ("compile-fail", "Test case is expected to fail to compile with the expected errors"),
+    ("output", "Test case is expected to print the expected output when run"),
+];
+
+pub struct Failure {
+    pub test: Test,
+    pub message: String,
+}
+
+lazy_static! {
+    static ref WRITE: Mutex<()> = Mutex::new(());
+}
+
+// Writes a SARIF log with a result for each failed test, located at the test
+// file, with what trybuild printed about the failure as the message. Several
+// suites may share the log, so the results of an existing one are kept except
+// for those of the tests that were just run, which are replaced.
+pub fn write(path: &Path, ran: &[PathBuf], failures: &[Failure]) -> Result<()> {
+    let _guard = WRITE.lock().unwrap_or_else(PoisonError::into_inner);
+
+    let mut log = log(failures);
+    let existing = fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<Value>(&json).ok());
+    if let Some(existing) = existing {
+        merge(&mut log, existing, ran);
+    }
+
+    let json = serde_json::to_string_pretty(&log).expect("SARIF log is valid JSON");
+    fs::write(path, json + "\n")?;
+    Ok(())
+}
+
+fn merge(log: &mut Value, mut existing: Value, ran: &[PathBuf]) {
+    let ran: Vec<String> = ran.iter().map(|path| uri(path)).collect();
+    let mut results = match existing["runs"][0]["results"].take() {
+        Value::Array(results) => results,
+        _ => return,
+    };
+    results.retain(|result| {
+        let uri = &result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"];
+        !ran.iter().any(|ran| uri == ran.as_str())
+    });
+    if let Value::Array(new) = log["runs"][0]["results"].take() {
+        results.extend(new);
+    }
+    log["runs"][0]["results"] = Value::Array(results);
+}
+
+fn uri(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+fn log(failures: &[Failure]) -> Value {
+    let rules: Vec<Value> = RULES
+        .iter()
+        .map(|(id, description)| {
+            json!({
+                "id": id,
+                "shortDescription": { "text": description },
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = failures
+        .iter()
+        .map(|failure| {
+            let rule = match failure.test.kind {
+                TestKind::Pass => 0,
+                TestKind::CompileFail => 1,
+                TestKind::Output => 2,
+            };
+            let uri = uri(&failure.test.path);
+            json!({
+                "ruleId": RULES[rule].0,
+                "ruleIndex": rule,
+                "level": "error",
+                "message": { "text": failure.message.trim() },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri },
+                    },
+                }],
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "trybuild",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/dtolnay/trybuild",
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Failure;
+    use crate::{Test, TestKind};
+    use std::path::PathBuf;
+
+    #[test]
+    fn log() {
+        let failure = Failure {
+            test: Test {
+                name: Test::gen_name(0),
+                path: PathBuf::from("tests/ui/leak.rs"),
+                kind: TestKind::CompileFail,
+                inline: None,
+                group: None,
+            },
+            message: "mismatch\n".to_owned(),
+        };
+        let log = super::log(&[failure]);
+        let result = &log["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "compile-fail");
+        assert_eq!(result["message"]["text"], "mismatch");
+        let location = &result["locations"][0]["physicalLocation"]["artifactLocation"];
+        assert_eq!(location["uri"], "tests/ui/leak.rs");
+    }
+
+    #[test]
+    fn merge() {
+        let failure = |path: &str| Failure {
+            test: Test {
+                name: Test::gen_name(0),
+                path: PathBuf::from(path),
+                kind: TestKind::CompileFail,
+                inline: None,
+                group: None,
+            },
+            message: "mismatch\n".to_owned(),
+        };
+        let existing = super::log(&[failure("tests/a.rs"), failure("tests/b.rs")]);
+        let mut log = super::log(&[failure("tests/c.rs")]);
+        super::merge(&mut log, existing, &[PathBuf::from("tests/b.rs")]);
+        let uris: Vec<&str> = log["runs"][0]["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| {
+                let location = &result["locations"][0]["physicalLocation"]["artifactLocation"];
+                location["uri"].as_str().unwrap()
+            })
+            .collect();
+        assert_eq!(uris, ["tests/a.rs", "tests/c.rs"]);
+    }
+}
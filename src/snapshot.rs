@@ -0,0 +1,653 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap as Map, BTreeSet as Set};
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use lazy_static::lazy_static;
+
+use crate::cargo::{self, Project};
+use crate::error::{Error, Result};
+use crate::options::Options;
+use crate::Test;
+
+// Expected output normally lives in a file next to the test, but may instead
+// be kept as one entry of a snapshot file shared by the whole suite, keyed by
+// the path the standalone file would have had, relative to the crate.
+
+pub fn read(project: &Project, path: &Path) -> Result<Option<String>> {
+    #[cfg(feature = "snap")]
+    {
+        if project.options.snap_files {
+            return snap::read(path);
+        }
+    }
+
+    let expected = match &project.options.snapshot_file {
+        Some(file) => load(file)?.remove(&key(&project.options, path)),
+        None => {
+            let file = file_path(&project.options, path);
+            consult(&file);
+            if file.exists() {
+                let content = read_file(&file)?;
+                Some(strip_header(&content).to_owned())
+            } else {
+                None
+            }
+        }
+    };
+
+    Ok(expected.map(|expected| expected.replace("\r\n", "\n")))
+}
+
+// Expected output that is not valid UTF-8, which is only ever kept in a
+// standalone file.
+pub fn read_bytes(options: &Options, path: &Path) -> Result<Option<Vec<u8>>> {
+    let file = mirror(options, path);
+    consult(&file);
+    if file.exists() {
+        fs::read(file).map(Some).map_err(Error::ReadStderr)
+    } else {
+        Ok(None)
+    }
+}
+
+// Records that a test read, or looked for, the expected file `file`, which
+// keeps it from being pruned as unused.
+fn consult(file: &Path) {
+    let mut consulted = CONSULTED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    consulted.insert(file.to_owned());
+}
+
+lazy_static! {
+    static ref CACHE: Mutex<Map<PathBuf, (SystemTime, String)>> = Mutex::new(Map::new());
+    static ref CONSULTED: Mutex<Set<PathBuf>> = Mutex::new(Set::new());
+}
+
+// Expected files are read once per run, or again if they were modified since.
+// A shared snapshot file would otherwise be read in full for every test.
+fn read_cached(path: &Path) -> io::Result<String> {
+    let modified = fs::metadata(path)?.modified()?;
+    let mut cache = CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some((cached_modified, content)) = cache.get(path) {
+        if *cached_modified == modified {
+            return Ok(content.clone());
+        }
+    }
+
+    let content = fs::read_to_string(path)?;
+    cache.insert(path.to_owned(), (modified, content.clone()));
+    Ok(content)
+}
+
+// The file holding the expected output for `path` when it is stored on its
+// own: `path` itself, or `path.gz` if compressed.
+pub fn file_path(options: &Options, path: &Path) -> PathBuf {
+    let path = &mirror(options, path);
+
+    #[cfg(feature = "gzip")]
+    {
+        let compressed = gzip::path(path);
+        if options.gzip || (!path.exists() && compressed.exists()) {
+            return compressed;
+        }
+    }
+
+    let _ = options;
+    path.to_owned()
+}
+
+// With `expected_dir`, expected files are kept in a tree of their own that
+// mirrors the tests, unless one is still next to its test from before.
+pub fn mirror(options: &Options, path: &Path) -> PathBuf {
+    let (tests, expected) = match &options.expected_dir {
+        Some(dirs) => dirs,
+        None => return path.to_owned(),
+    };
+
+    let resolve = |dir: &Path| match &options.base_dir {
+        Some(base_dir) => base_dir.join(dir),
+        None => dir.to_owned(),
+    };
+    let relative = match path.strip_prefix(resolve(tests)) {
+        Ok(relative) => relative,
+        Err(_) => return path.to_owned(),
+    };
+
+    let mirrored = resolve(expected).join(relative);
+    if mirrored.exists() || !sibling_exists(path) {
+        mirrored
+    } else {
+        path.to_owned()
+    }
+}
+
+fn sibling_exists(path: &Path) -> bool {
+    #[cfg(feature = "gzip")]
+    {
+        if gzip::path(path).exists() {
+            return true;
+        }
+    }
+
+    path.exists()
+}
+
+fn read_file(file: &Path) -> Result<String> {
+    #[cfg(feature = "gzip")]
+    {
+        if gzip::is_compressed(file) {
+            return gzip::read(file);
+        }
+    }
+
+    read_cached(file).map_err(Error::ReadStderr)
+}
+
+// Writes a file that may be in the cache. The modification time alone is not
+// enough to tell that it changed on filesystems with a coarse clock.
+pub fn write_file(file: &Path, content: &str) -> Result<()> {
+    let mut cache = CACHE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    cache.remove(file);
+
+    #[cfg(feature = "gzip")]
+    {
+        if gzip::is_compressed(file) {
+            return gzip::write(file, content);
+        }
+    }
+
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir).map_err(Error::WriteStderr)?;
+    }
+    fs::write(file, content).map_err(Error::WriteStderr)
+}
+
+// Whether there is expected output for `path`, for listing tests before any
+// project is prepared.
+pub fn exists(options: &Options, path: &Path) -> bool {
+    #[cfg(feature = "snap")]
+    {
+        if options.snap_files {
+            return snap::snap_path(path).exists();
+        }
+    }
+
+    match &options.snapshot_file {
+        Some(file) => {
+            file.exists() && load(file).map_or(false, |snapshots| snapshots.contains_key(&key(options, path)))
+        }
+        None => file_path(options, path).exists(),
+    }
+}
+
+// Expected output files in the directories of the tests, or in the mirrored
+// ones, which belong to none of the tests.
+pub fn orphans(options: &Options, tests: &[Test]) -> Vec<PathBuf> {
+    scan(options, tests, |_, owner| owner.is_none())
+}
+
+// The expected output files of the tests that were run which none of them
+// read, like the _*.stderr_ of a test that now compiles. Files of no test at
+// all may belong to another suite sharing the directory, so they are only
+// included with `check_orphans`.
+pub fn unused(options: &Options, tests: &[Test], ran: &[Test]) -> Vec<PathBuf> {
+    let consulted = CONSULTED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    scan(options, tests, |file, owner| match owner {
+        Some(owner) => ran.iter().any(|test| test.name == owner.name) && !consulted.contains(file),
+        None => options.check_orphans,
+    })
+}
+
+// Looks through the directories of the tests for expected output files, and
+// keeps those that `select` picks given the test they belong to, if any. Only
+// standalone files are looked at; a snapshot file is left alone.
+fn scan<F>(options: &Options, tests: &[Test], select: F) -> Vec<PathBuf>
+where
+    F: Fn(&Path, Option<&Test>) -> bool,
+{
+    #[cfg(feature = "snap")]
+    {
+        if options.snap_files {
+            return Vec::new();
+        }
+    }
+
+    if options.snapshot_file.is_some() {
+        return Vec::new();
+    }
+
+    let owners: Map<PathBuf, &Test> = tests
+        .iter()
+        .filter(|test| test.inline.is_none())
+        .flat_map(|test| expected_files(options, test).into_iter().map(move |file| (file, test)))
+        .collect();
+    let dirs: Set<&Path> = owners.keys().filter_map(|file| file.parent()).collect();
+
+    let exts = expected_exts(options);
+    let mut selected = Vec::new();
+    for dir in dirs {
+        let read_dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+        let entries = match fs::read_dir(read_dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let file = dir.join(entry.file_name());
+            let is_expected = file
+                .extension()
+                .map_or(false, |ext| exts.iter().any(|expected| ext == OsStr::new(expected)));
+            if is_expected && file.is_file() && select(&file, owners.get(&file).copied()) {
+                selected.push(file);
+            }
+        }
+    }
+
+    selected.sort();
+    selected
+}
+
+fn expected_exts(options: &Options) -> [&str; 2] {
+    [options.stderr_ext.as_deref().unwrap_or("stderr"), "stdout"]
+}
+
+// Where the expected output of a test may be: next to it, or in the mirrored
+// directory.
+fn expected_files(options: &Options, test: &Test) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for ext in &expected_exts(options) {
+        let file = test.path.with_extension(ext);
+        let mirrored = mirror(options, &file);
+        if mirrored != file {
+            files.push(mirrored);
+        }
+        files.push(file);
+    }
+    files
+}
+
+// Whether the expected output lives in its own file, the only place that can
+// hold output which is not valid UTF-8.
+pub fn standalone(project: &Project) -> bool {
+    #[cfg(feature = "snap")]
+    {
+        if project.options.snap_files {
+            return false;
+        }
+    }
+
+    #[cfg(feature = "gzip")]
+    {
+        if project.options.gzip {
+            return false;
+        }
+    }
+
+    project.options.snapshot_file.is_none()
+}
+
+pub fn write(project: &Project, path: &Path, content: &str) -> Result<()> {
+    #[cfg(feature = "snap")]
+    {
+        if project.options.snap_files {
+            return snap::write(&snap::snap_path(path), path, content);
+        }
+    }
+
+    match &project.options.snapshot_file {
+        Some(file) => update(&project.options, file, file, path, content),
+        None => write_file(&file_path(&project.options, path), &with_header(project, content)),
+    }
+}
+
+lazy_static! {
+    static ref HEADER: String = format!(
+        "{} {}, {}\n",
+        HEADER_PREFIX,
+        env!("CARGO_PKG_VERSION"),
+        rustc_version().unwrap_or_else(|| "rustc unknown".to_owned()),
+    );
+}
+
+// Standalone expected files may start with a line recording the versions
+// that produced them. It is written only when asked for, but always skipped
+// when reading, so that files with and without one both keep working. Only a
+// line of exactly the generated shape is skipped, since expected output may
+// well start with a comment of its own.
+const HEADER_PREFIX: &str = "// trybuild";
+
+pub fn with_header<'a>(project: &Project, content: &'a str) -> Cow<'a, str> {
+    if project.options.snapshot_header {
+        Cow::Owned(format!("{}{}", *HEADER, content))
+    } else {
+        Cow::Borrowed(content)
+    }
+}
+
+fn strip_header(content: &str) -> &str {
+    let (first, rest) = match content.find('\n') {
+        Some(end) => (&content[..end], &content[end + 1..]),
+        None => (content, ""),
+    };
+    if is_header(first) {
+        rest
+    } else {
+        content
+    }
+}
+
+// `// trybuild 1.0.11, rustc 1.70.0 (90c541806 2023-05-31)`
+fn is_header(line: &str) -> bool {
+    let rest = match line.strip_prefix(HEADER_PREFIX).and_then(|rest| rest.strip_prefix(' ')) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let comma = match rest.find(", ") {
+        Some(comma) => comma,
+        None => return false,
+    };
+    let version = &rest[..comma];
+    let is_version = version.starts_with(|ch: char| ch.is_ascii_digit())
+        && version
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '.' || ch == '-' || ch == '+');
+    is_version && rest[comma + ", ".len()..].starts_with("rustc ")
+}
+
+fn rustc_version() -> Option<String> {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let output = Command::new(rustc).arg("-V").output().ok()?;
+    let version = String::from_utf8(output.stdout).ok()?;
+    Some(version.trim().to_owned())
+}
+
+// Saves output that has not been accepted yet wherever the configured review
+// tooling expects to find it. Returns the path of the saved file, or None if
+// there is no such tooling and the caller should fall back to the wip dir.
+pub fn write_pending(project: &Project, path: &Path, content: &str) -> Result<Option<PathBuf>> {
+    #[cfg(feature = "snap")]
+    {
+        if project.options.snap_files {
+            let pending = snap::snap_path(path).with_extension("snap.new");
+            snap::write(&pending, path, content)?;
+            return Ok(Some(pending));
+        }
+    }
+
+    let _ = (project, path, content);
+    Ok(None)
+}
+
+// Reads the snapshot file at `src`, sets the entry for `path`, and writes the
+// result to `dst`.
+pub fn update(options: &Options, src: &Path, dst: &Path, path: &Path, content: &str) -> Result<()> {
+    let mut snapshots = load(src)?;
+    snapshots.insert(key(options, path), content.to_owned());
+    store(dst, &snapshots)
+}
+
+// Paths made absolute by `base_dir` would tie the snapshot file to one
+// machine, so keys are relative to the crate, or else to `base_dir`.
+fn key(options: &Options, path: &Path) -> String {
+    let crate_dir = cargo::source_dir(options).ok();
+    let relative = crate_dir
+        .iter()
+        .map(PathBuf::as_path)
+        .chain(options.base_dir.as_deref())
+        .find_map(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+fn is_json(file: &Path) -> bool {
+    file.extension() == Some(OsStr::new("json"))
+}
+
+fn load(file: &Path) -> Result<Map<String, String>> {
+    if !file.exists() {
+        return Ok(Map::new());
+    }
+
+    let content = read_cached(file).map_err(Error::ReadStderr)?;
+    let snapshots = if is_json(file) {
+        serde_json::from_str(&content).map_err(|e| e.to_string())
+    } else {
+        toml::from_str(&content).map_err(|e| e.to_string())
+    };
+
+    snapshots.map_err(|e| Error::Snapshot(file.to_owned(), e))
+}
+
+fn store(file: &Path, snapshots: &Map<String, String>) -> Result<()> {
+    let content = if is_json(file) {
+        serde_json::to_string_pretty(snapshots).map_err(|e| e.to_string())
+    } else {
+        toml::to_string_pretty(snapshots).map_err(|e| e.to_string())
+    };
+
+    let content = content.map_err(|e| Error::Snapshot(file.to_owned(), e))?;
+    write_file(file, &content)
+}
+
+#[cfg(feature = "gzip")]
+mod gzip {
+    use std::ffi::OsStr;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::path::{Path, PathBuf};
+
+    use flate2::read::GzDecoder;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    use crate::error::{Error, Result};
+
+    // tests/ui/foo.stderr -> tests/ui/foo.stderr.gz
+    pub fn path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".gz");
+        PathBuf::from(name)
+    }
+
+    pub fn is_compressed(file: &Path) -> bool {
+        file.extension() == Some(OsStr::new("gz"))
+    }
+
+    pub fn read(file: &Path) -> Result<String> {
+        let compressed = File::open(file).map_err(Error::ReadStderr)?;
+        let mut content = String::new();
+        GzDecoder::new(compressed)
+            .read_to_string(&mut content)
+            .map_err(Error::ReadStderr)?;
+        Ok(content)
+    }
+
+    pub fn write(file: &Path, content: &str) -> Result<()> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(content.as_bytes()).map_err(Error::WriteStderr)?;
+        let compressed = encoder.finish().map_err(Error::WriteStderr)?;
+        fs::write(file, compressed).map_err(Error::WriteStderr)
+    }
+}
+
+#[cfg(feature = "snap")]
+mod snap {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use crate::error::{Error, Result};
+
+    // tests/ui/foo.stderr -> tests/ui/snapshots/foo.stderr.snap
+    pub fn snap_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().expect("expected file name").to_owned();
+        name.push(".snap");
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        dir.join("snapshots").join(name)
+    }
+
+    pub fn read(path: &Path) -> Result<Option<String>> {
+        let snap = snap_path(path);
+        if !snap.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&snap).map_err(Error::ReadStderr)?;
+        let content = content.replace("\r\n", "\n");
+        match strip_header(&content) {
+            Some(body) => Ok(Some(body.to_owned())),
+            None => Err(Error::Snapshot(snap, "missing `---` header".to_owned())),
+        }
+    }
+
+    pub fn write(snap: &Path, path: &Path, content: &str) -> Result<()> {
+        let source = path.with_extension("rs");
+        let expression = path.extension().unwrap_or_default().to_string_lossy();
+        let header = format!(
+            "---\nsource: {}\nexpression: {}\n---\n",
+            source.to_string_lossy().replace('\\', "/"),
+            expression,
+        );
+
+        if let Some(dir) = snap.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(snap, header + content).map_err(Error::WriteStderr)
+    }
+
+    // The header is a block of metadata between two `---` lines.
+    pub fn strip_header(content: &str) -> Option<&str> {
+        let rest = content.strip_prefix("---\n")?;
+        let end = rest.find("\n---\n")?;
+        Some(&rest[end + "\n---\n".len()..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::Path;
+    use std::slice;
+
+    #[test]
+    fn header() {
+        let stderr = "error: ERROR\n";
+        let with_header = format!("// trybuild 1.0.0, rustc 1.70.0 (90c541806 2023-05-31)\n{}", stderr);
+        assert_eq!(super::strip_header(&with_header), stderr);
+        assert_eq!(super::strip_header(stderr), stderr);
+
+        let stdout = "// trybuild is printed by the test\n";
+        assert_eq!(super::strip_header(stdout), stdout);
+        let unknown = "// trybuild 1.0.0-rc.1, rustc unknown\nout\n";
+        assert_eq!(super::strip_header(unknown), "out\n");
+    }
+
+    #[test]
+    fn roundtrip() {
+        let dir = env::temp_dir().join("trybuild-snapshot-roundtrip");
+        fs::create_dir_all(&dir).unwrap();
+
+        for name in &["snapshots.toml", "snapshots.json"] {
+            let file = dir.join(name);
+            let _ = fs::remove_file(&file);
+
+            let options = crate::options::Options::default();
+            let absolute = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/ui/c.stderr");
+            let stderr = "error: ERROR\n --> $DIR/a.rs:1:1\n";
+            super::update(&options, &file, &file, Path::new("tests\\ui\\a.stderr"), stderr).unwrap();
+            super::update(&options, &file, &file, Path::new("tests/ui/b.stdout"), "b\n").unwrap();
+            super::update(&options, &file, &file, &absolute, "c\n").unwrap();
+
+            let snapshots = super::load(&file).unwrap();
+            assert_eq!(snapshots["tests/ui/a.stderr"], stderr);
+            assert_eq!(snapshots["tests/ui/b.stdout"], "b\n");
+            assert_eq!(snapshots["tests/ui/c.stderr"], "c\n");
+        }
+    }
+
+    #[test]
+    fn mirror() {
+        let dir = env::temp_dir().join("trybuild-snapshot-mirror");
+        let tests = dir.join("ui");
+        let expected = dir.join("ui-expected");
+        fs::create_dir_all(tests.join("nested")).unwrap();
+        let _ = fs::remove_dir_all(&expected);
+
+        let options = crate::options::Options {
+            expected_dir: Some((tests.clone(), expected.clone())),
+            ..Default::default()
+        };
+
+        let sibling = tests.join("nested").join("a.stderr");
+        let _ = fs::remove_file(&sibling);
+        let mirrored = expected.join("nested").join("a.stderr");
+        assert_eq!(super::mirror(&options, &sibling), mirrored);
+
+        fs::write(&sibling, "error: ERROR\n").unwrap();
+        assert_eq!(super::mirror(&options, &sibling), sibling);
+
+        super::write_file(&mirrored, "error: ERROR\n").unwrap();
+        assert_eq!(super::mirror(&options, &sibling), mirrored);
+
+        let outside = dir.join("other").join("a.stderr");
+        assert_eq!(super::mirror(&options, &outside), outside);
+    }
+
+    #[test]
+    fn orphans() {
+        let dir = env::temp_dir().join("trybuild-snapshot-orphans");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["a.rs", "a.stderr", "b.stderr", "b.stdout", "notes.md"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let test = crate::Test {
+            name: crate::Test::gen_name(0),
+            path: dir.join("a.rs"),
+            kind: crate::TestKind::CompileFail,
+            inline: None,
+            group: None,
+        };
+        let options = crate::options::Options::default();
+        let orphans = super::orphans(&options, slice::from_ref(&test));
+        assert_eq!(orphans, [dir.join("b.stderr"), dir.join("b.stdout")]);
+
+        let unused = super::unused(&options, slice::from_ref(&test), slice::from_ref(&test));
+        assert_eq!(unused, [dir.join("a.stderr")]);
+
+        let options = crate::options::Options {
+            check_orphans: true,
+            ..Default::default()
+        };
+        let unused = super::unused(&options, slice::from_ref(&test), slice::from_ref(&test));
+        assert_eq!(unused, [dir.join("a.stderr"), dir.join("b.stderr"), dir.join("b.stdout")]);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip() {
+        let dir = env::temp_dir().join("trybuild-snapshot-gzip");
+        fs::create_dir_all(&dir).unwrap();
+
+        let file = super::gzip::path(&dir.join("a.stderr"));
+        assert!(file.to_string_lossy().ends_with("a.stderr.gz"));
+
+        super::write_file(&file, "error: ERROR\n").unwrap();
+        assert_ne!(fs::read(&file).unwrap(), b"error: ERROR\n");
+        assert_eq!(super::read_file(&file).unwrap(), "error: ERROR\n");
+    }
+
+    #[cfg(feature = "snap")]
+    #[test]
+    fn snap_header() {
+        let snap = "---\nsource: tests/ui/a.rs\nexpression: stderr\n---\nerror: ERROR\n";
+        assert_eq!(super::snap::strip_header(snap), Some("error: ERROR\n"));
+        assert_eq!(super::snap::strip_header("error: ERROR\n"), None);
+    }
+}
@@ -1,16 +1,187 @@
+use std::cell::RefCell;
+use std::env;
+use std::io::{self, Write};
 use std::sync::{Mutex, MutexGuard, PoisonError};
 
 use lazy_static::lazy_static;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream as Stream, WriteColor};
+use termcolor::{Buffer, BufferWriter, Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 lazy_static! {
-    static ref TERM: Mutex<Stream> = Mutex::new(Stream::stderr(ColorChoice::Auto));
+    static ref TERM: Mutex<Term> = Mutex::new(Term {
+        stream: StandardStream::stderr(ColorChoice::Auto),
+    });
+    static ref ASCII: bool = detect_ascii();
 }
 
-pub fn lock() -> MutexGuard<'static, Stream> {
+// Each `TestCases` runs on the thread of its own #[test], so the ways in which
+// output is diverted are kept per thread. That way suites running in parallel
+// never collect, hold back or drop each other's output.
+thread_local! {
+    static MODES: RefCell<Modes> = RefCell::new(Modes::default());
+}
+
+pub struct Term {
+    stream: StandardStream,
+}
+
+#[derive(Default)]
+struct Modes {
+    // While set, output is collected here rather than shown, e.g. to be
+    // reformatted as a TAP diagnostic.
+    capture: Option<Buffer>,
+    // Output that is shown later only if it turns out to matter, colors and
+    // all.
+    held: Option<Buffer>,
+    // A plain copy of the output, kept while it is also shown as usual, e.g.
+    // for the message of a SARIF result.
+    record: Option<Buffer>,
+}
+
+fn modes<T>(f: impl FnOnce(&mut Modes) -> T) -> T {
+    MODES.with(|modes| f(&mut modes.borrow_mut()))
+}
+
+fn take_text(buffer: &mut Option<Buffer>) -> String {
+    match buffer {
+        Some(buffer) => {
+            let text = String::from_utf8_lossy(buffer.as_slice()).into_owned();
+            buffer.clear();
+            text
+        }
+        None => String::new(),
+    }
+}
+
+pub fn lock() -> MutexGuard<'static, Term> {
     TERM.lock().unwrap_or_else(PoisonError::into_inner)
 }
 
+/// Starts collecting subsequent output instead of printing it.
+pub fn capture() {
+    modes(|modes| {
+        modes.capture.get_or_insert_with(Buffer::no_color);
+    });
+}
+
+/// Returns the output collected since the last call, leaving capture on.
+pub fn take_captured() -> String {
+    modes(|modes| take_text(&mut modes.capture))
+}
+
+/// Holds back subsequent output until `release` or `discard`.
+pub fn hold() {
+    modes(|modes| {
+        modes.held.get_or_insert_with(|| BufferWriter::stderr(ColorChoice::Auto).buffer());
+    });
+}
+
+/// Shows the output held back since `hold` and stops holding.
+pub fn release() {
+    let held = modes(|modes| modes.held.take());
+    if let Some(buffer) = held {
+        let mut term = lock();
+        let _ = term.flush();
+        let _ = BufferWriter::stderr(ColorChoice::Auto).print(&buffer);
+    }
+}
+
+/// Drops the output held back since `hold` and stops holding.
+pub fn discard() {
+    modes(|modes| modes.held = None);
+}
+
+/// Starts keeping a copy of subsequent output, which is still shown.
+pub fn record() {
+    modes(|modes| {
+        modes.record.get_or_insert_with(Buffer::no_color);
+    });
+}
+
+/// Stops keeping a copy of the output.
+pub fn stop_recording() {
+    modes(|modes| modes.record = None);
+}
+
+/// Returns the output kept since the last call, leaving recording on.
+pub fn take_recorded() -> String {
+    modes(|modes| take_text(&mut modes.record))
+}
+
+impl Term {
+    fn output<T>(&mut self, f: impl FnOnce(&mut dyn WriteColor) -> T) -> T {
+        modes(|modes| match (&mut modes.capture, &mut modes.held) {
+            (Some(buffer), _) | (None, Some(buffer)) => f(buffer),
+            (None, None) => f(&mut self.stream),
+        })
+    }
+}
+
+impl Write for Term {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.output(|output| output.write(buf))?;
+        modes(|modes| match &mut modes.record {
+            Some(record) => record.write_all(&buf[..n]),
+            None => Ok(()),
+        })?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output(|output| output.flush())
+    }
+}
+
+impl WriteColor for Term {
+    fn supports_color(&self) -> bool {
+        modes(|modes| match (&modes.capture, &modes.held) {
+            (Some(buffer), _) | (None, Some(buffer)) => buffer.supports_color(),
+            (None, None) => self.stream.supports_color(),
+        })
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> io::Result<()> {
+        self.output(|output| output.set_color(spec))
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.output(|output| output.reset())
+    }
+}
+
+/// Width of the terminal in columns, if known.
+///
+/// Cargo captures the test's stderr in a way that hides the real terminal from
+/// us, so this relies on the `COLUMNS` variable exported by most shells.
+pub fn width() -> Option<usize> {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.trim().parse().ok())
+        .filter(|&columns| columns > 0)
+}
+
+/// Whether output should avoid non-ASCII characters like box drawing.
+pub fn ascii() -> bool {
+    *ASCII
+}
+
+fn detect_ascii() -> bool {
+    if env::var_os("TRYBUILD_ASCII").is_some() {
+        return crate::env::enabled("TRYBUILD_ASCII");
+    }
+
+    // Same precedence as setlocale: the first of these that is set decides.
+    for var in &["LC_ALL", "LC_CTYPE", "LANG"] {
+        if let Ok(locale) = env::var(var) {
+            if !locale.is_empty() {
+                let locale = locale.to_ascii_lowercase();
+                return !locale.contains("utf-8") && !locale.contains("utf8");
+            }
+        }
+    }
+
+    false
+}
+
 pub fn bold() {
     let _ = lock().set_color(ColorSpec::new().set_bold(true));
 }
@@ -0,0 +1,174 @@
+// Expected output may contain placeholders standing in for text that varies
+// between runs. Both `[...]` and `<IGNORE>` match any run of characters
+// within their line. A literal `[...]` is written as `\[...]`.
+
+const PLACEHOLDERS: &[&str] = &["[...]", "<IGNORE>"];
+const ESCAPED: &str = "\\[...]";
+
+enum Piece<'a> {
+    Literal(&'a str),
+    Wildcard,
+}
+
+pub fn matches(expected: &str, actual: &str) -> bool {
+    if !has_placeholder(expected) {
+        return expected == actual;
+    }
+
+    let same_shape = expected.lines().count() == actual.lines().count()
+        && expected.ends_with('\n') == actual.ends_with('\n');
+
+    same_shape
+        && expected
+            .lines()
+            .zip(actual.lines())
+            .all(|(expected, actual)| line_matches(expected, actual))
+}
+
+// Produces the text to write in place of `expected` when overwriting it with
+// `actual`, keeping expected lines whose placeholders still match so that a
+// bless run does not replace them with concrete text.
+pub fn preserve(expected: &str, actual: &str) -> String {
+    if !has_placeholder(expected) {
+        return actual.to_owned();
+    }
+
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual_lines: Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence where lines are equal if the expected line
+    // matches the actual one.
+    let (n, m) = (expected.len(), actual_lines.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if line_matches(expected[i], actual_lines[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut preserved = String::new();
+    let (mut i, mut j) = (0, 0);
+    while j < m {
+        let line = if i < n && line_matches(expected[i], actual_lines[j]) {
+            i += 1;
+            expected[i - 1]
+        } else if i < n && lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+            continue;
+        } else {
+            actual_lines[j]
+        };
+        preserved += line;
+        preserved.push('\n');
+        j += 1;
+    }
+
+    if !actual.ends_with('\n') {
+        preserved.pop();
+    }
+    preserved
+}
+
+fn has_placeholder(expected: &str) -> bool {
+    PLACEHOLDERS.iter().any(|placeholder| expected.contains(placeholder))
+}
+
+fn parse(line: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut literal_start = 0;
+    let mut i = 0;
+
+    'scan: while i < line.len() {
+        if line[i..].starts_with(ESCAPED) {
+            pieces.push(Piece::Literal(&line[literal_start..i]));
+            literal_start = i + 1;
+            i += ESCAPED.len();
+            continue;
+        }
+        for placeholder in PLACEHOLDERS {
+            if line[i..].starts_with(placeholder) {
+                pieces.push(Piece::Literal(&line[literal_start..i]));
+                pieces.push(Piece::Wildcard);
+                i += placeholder.len();
+                literal_start = i;
+                continue 'scan;
+            }
+        }
+        i += line[i..].chars().next().map_or(1, char::len_utf8);
+    }
+
+    pieces.push(Piece::Literal(&line[literal_start..]));
+    pieces
+}
+
+fn line_matches(expected: &str, actual: &str) -> bool {
+    let mut rest = actual;
+    let mut anchored = true;
+
+    let pieces = parse(expected);
+    let last = pieces.len() - 1;
+    for (i, piece) in pieces.iter().enumerate() {
+        let literal = match piece {
+            Piece::Wildcard => {
+                anchored = false;
+                continue;
+            }
+            Piece::Literal(literal) => *literal,
+        };
+
+        if anchored {
+            if !rest.starts_with(literal) {
+                return false;
+            }
+            rest = &rest[literal.len()..];
+        } else if i == last {
+            // The final piece must sit at the very end of the line.
+            return rest.ends_with(literal);
+        } else {
+            // Taking the leftmost occurrence of each literal piece never rules
+            // out a match that some later occurrence would have allowed.
+            match rest.find(literal) {
+                Some(start) => rest = &rest[start + literal.len()..],
+                None => return false,
+            }
+        }
+        anchored = true;
+    }
+
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, preserve};
+
+    #[test]
+    fn placeholders() {
+        let expected = "error: capacity <IGNORE> exceeded\n --> $DIR/a.rs:1:1\n";
+        assert!(matches(expected, "error: capacity 1024 exceeded\n --> $DIR/a.rs:1:1\n"));
+        assert!(matches(expected, "error: capacity  exceeded\n --> $DIR/a.rs:1:1\n"));
+        assert!(!matches(expected, "error: capacity 1024\n --> $DIR/a.rs:1:1\n"));
+        assert!(!matches(expected, "error: capacity 1024 exceeded\n"));
+        assert!(matches("<IGNORE>: <IGNORE>!\n", "error: oops!\n"));
+        assert!(!matches("ab<IGNORE>ba\n", "aba\n"));
+
+        assert!(matches("note: required by [...]\n", "note: required by `Foo`\n"));
+        assert!(matches("[...] `[...]`\n", "error: `x`\n"));
+        assert!(matches("array \\[...]\n", "array [...]\n"));
+        assert!(!matches("array \\[...]\n", "array [1, 2]\n"));
+    }
+
+    #[test]
+    fn preserve_placeholders() {
+        let expected = "error: capacity [...] exceeded\n --> $DIR/a.rs:1:1\n";
+        let actual = "error: capacity 9 exceeded\n --> $DIR/a.rs:2:1\nnote: new\n";
+        assert_eq!(
+            preserve(expected, actual),
+            "error: capacity [...] exceeded\n --> $DIR/a.rs:2:1\nnote: new\n",
+        );
+    }
+}